@@ -12,6 +12,7 @@ use std::task::Context;
 use std::task::Poll;
 use tokio::sync::mpsc;
 use tokio::time::timeout;
+use tokio_util::sync::CancellationToken;
 use tracing::debug;
 use tracing::trace;
 
@@ -37,11 +38,15 @@ pub(crate) async fn stream_chat_completions(
     let mut messages = Vec::<serde_json::Value>::new();
 
     let full_instructions = prompt.get_full_instructions(model);
-    messages.push(json!({"role": "system", "content": full_instructions}));
+    messages.push(json!({"role": "system", "content": &*full_instructions}));
 
     for item in &prompt.input {
         match item {
-            ResponseItem::Message { role, content } => {
+            ResponseItem::Message {
+                role,
+                content,
+                name,
+            } => {
                 let mut text = String::new();
                 for c in content {
                     match c {
@@ -52,7 +57,11 @@ pub(crate) async fn stream_chat_completions(
                         _ => {}
                     }
                 }
-                messages.push(json!({"role": role, "content": text}));
+                let mut message = json!({"role": role, "content": text});
+                if let Some(name) = name {
+                    message["name"] = json!(name);
+                }
+                messages.push(message);
             }
             ResponseItem::FunctionCall {
                 name,
@@ -105,12 +114,15 @@ pub(crate) async fn stream_chat_completions(
     }
 
     let tools_json = create_tools_json_for_chat_completions_api(prompt, model)?;
-    let payload = json!({
+    let mut payload = json!({
         "model": model,
         "messages": messages,
         "stream": true,
         "tools": tools_json,
     });
+    if let Some(stop) = prompt.stop_sequences(provider.max_stop_sequences())? {
+        payload["stop"] = json!(stop);
+    }
 
     debug!(
         "POST to {}: {}",
@@ -134,13 +146,18 @@ pub(crate) async fn stream_chat_completions(
         match res {
             Ok(resp) if resp.status().is_success() => {
                 let (tx_event, rx_event) = mpsc::channel::<Result<ResponseEvent>>(1600);
+                let cancel_token = CancellationToken::new();
                 let stream = resp.bytes_stream().map_err(CodexErr::Reqwest);
                 tokio::spawn(process_chat_sse(
                     stream,
                     tx_event,
                     provider.stream_idle_timeout(),
+                    cancel_token.clone(),
                 ));
-                return Ok(ResponseStream { rx_event });
+                return Ok(ResponseStream {
+                    rx_event,
+                    cancel_token,
+                });
             }
             Ok(res) => {
                 let status = res.status();
@@ -182,6 +199,7 @@ async fn process_chat_sse<S>(
     stream: S,
     tx_event: mpsc::Sender<Result<ResponseEvent>>,
     idle_timeout: Duration,
+    cancel_token: CancellationToken,
 ) where
     S: Stream<Item = Result<Bytes>> + Unpin,
 {
@@ -203,7 +221,14 @@ async fn process_chat_sse<S>(
     let mut fn_call_state = FunctionCallState::default();
 
     loop {
-        let sse = match timeout(idle_timeout, stream.next()).await {
+        let next = tokio::select! {
+            _ = cancel_token.cancelled() => {
+                trace!("chat completions stream cancelled");
+                return;
+            }
+            next = timeout(idle_timeout, stream.next()) => next,
+        };
+        let sse = match next {
             Ok(Some(Ok(ev))) => ev,
             Ok(Some(Err(e))) => {
                 let _ = tx_event.send(Err(CodexErr::Stream(e.to_string()))).await;
@@ -259,6 +284,7 @@ async fn process_chat_sse<S>(
                     content: vec![ContentItem::OutputText {
                         text: content.to_string(),
                     }],
+                    name: None,
                 };
 
                 let _ = tx_event.send(Ok(ResponseEvent::OutputItemDone(item))).await;
@@ -406,6 +432,7 @@ where
                             content: vec![crate::models::ContentItem::OutputText {
                                 text: std::mem::take(&mut this.cumulative),
                             }],
+                            name: None,
                         };
 
                         // Buffer Completed so it is returned *after* the aggregated message.
@@ -425,13 +452,20 @@ where
                         token_usage,
                     })));
                 }
+                Poll::Ready(Some(Ok(
+                    ev @ (ResponseEvent::ProviderFailover { .. }
+                    | ResponseEvent::ReasoningEscalated { .. }
+                    | ResponseEvent::ResponseHeaders(_)),
+                ))) => {
+                    return Poll::Ready(Some(Ok(ev)));
+                }
                 Poll::Ready(Some(Ok(ResponseEvent::Created))) => {
                     // These events are exclusive to the Responses API and
                     // will never appear in a Chat Completions stream.
                     continue;
                 }
                 Poll::Ready(Some(Ok(ResponseEvent::OutputTextDelta(_))))
-                | Poll::Ready(Some(Ok(ResponseEvent::ReasoningSummaryDelta(_)))) => {
+                | Poll::Ready(Some(Ok(ResponseEvent::ReasoningSummaryDelta { .. }))) => {
                     // Deltas are ignored here since aggregation waits for the
                     // final OutputItemDone.
                     continue;
@@ -1,6 +1,8 @@
+use std::collections::HashMap;
 use std::io::BufRead;
 use std::path::Path;
 use std::time::Duration;
+use std::time::Instant;
 
 use bytes::Bytes;
 use eventsource_stream::Eventsource;
@@ -12,6 +14,8 @@ use serde_json::Value;
 use tokio::sync::mpsc;
 use tokio::time::timeout;
 use tokio_util::io::ReaderStream;
+use tokio_util::sync::CancellationToken;
+use tracing::Instrument;
 use tracing::debug;
 use tracing::trace;
 use tracing::warn;
@@ -24,16 +28,26 @@ use crate::client_common::ResponseEvent;
 use crate::client_common::ResponseStream;
 use crate::client_common::ResponsesApiRequest;
 use crate::client_common::create_reasoning_param_for_request;
+use crate::client_common::has_visible_text;
+use crate::client_common::model_name_supports_reasoning_summaries;
+use crate::client_common::next_reasoning_effort;
+use crate::client_common::with_reasoning_summary_language;
 use crate::config::Config;
 use crate::config_types::ReasoningEffort as ReasoningEffortConfig;
 use crate::config_types::ReasoningSummary as ReasoningSummaryConfig;
+use crate::conversation_tracing::create_llm_request_span;
+use crate::conversation_tracing::record_error;
+use crate::conversation_tracing::record_llm_response;
+use crate::conversation_tracing::record_rate_limits;
+use crate::conversation_tracing::record_retry_count;
+use crate::conversation_tracing::record_token_usage;
 use crate::error::CodexErr;
 use crate::error::Result;
 use crate::flags::CODEX_RS_SSE_FIXTURE;
 use crate::model_provider_info::ModelProviderInfo;
 use crate::model_provider_info::WireApi;
 use crate::models::ResponseItem;
-use crate::openai_tools::create_tools_json_for_responses_api;
+use crate::openai_tools::create_tools_for_responses_api;
 use crate::protocol::TokenUsage;
 use crate::util::backoff;
 use std::sync::Arc;
@@ -69,16 +83,157 @@ impl ModelClient {
     /// Dispatches to either the Responses or Chat implementation depending on
     /// the provider config.  Public callers always invoke `stream()` – the
     /// specialised helpers are private to avoid accidental misuse.
+    ///
+    /// If the provider is unreachable or keeps failing with server errors
+    /// after its retries are exhausted, the same prompt is sent to each
+    /// provider listed in `provider_fallback` in turn. The returned stream
+    /// then starts with one `ProviderFailover` event per switch.
+    ///
+    /// With `escalate_reasoning_on_empty`, a response that completes without
+    /// any visible output is re-issued once at the next-higher reasoning
+    /// effort; the stream then continues with a `ReasoningEscalated` event
+    /// followed by the events of the retry.
     pub async fn stream(&self, prompt: &Prompt) -> Result<ResponseStream> {
-        match self.provider.wire_api {
-            WireApi::Responses => self.stream_responses(prompt).await,
+        let stream = self.stream_with_effort(prompt, self.effort).await?;
+        match self.escalated_effort(prompt) {
+            Some(to) => Ok(self.escalate_on_empty(stream, prompt, to)),
+            None => Ok(stream),
+        }
+    }
+
+    /// Effort to retry an empty response with, if escalation applies.
+    fn escalated_effort(&self, prompt: &Prompt) -> Option<ReasoningEffortConfig> {
+        if !self.config.escalate_reasoning_on_empty
+            || self.provider.wire_api != WireApi::Responses
+            || !model_name_supports_reasoning_summaries(
+                &self.config,
+                prompt.model(&self.config.model),
+            )
+        {
+            return None;
+        }
+        next_reasoning_effort(self.effort)
+    }
+
+    /// Forwards `stream`, but if it completes without visible output the
+    /// `Completed` event is swallowed and the prompt is re-sent at effort `to`.
+    fn escalate_on_empty(
+        &self,
+        mut stream: ResponseStream,
+        prompt: &Prompt,
+        to: ReasoningEffortConfig,
+    ) -> ResponseStream {
+        let (tx, rx) = mpsc::channel::<Result<ResponseEvent>>(16);
+        let cancel_token = CancellationToken::new();
+        let client = self.clone();
+        let prompt = prompt.clone();
+        let from = self.effort;
+
+        tokio::spawn({
+            let cancel_token = cancel_token.clone();
+            async move {
+                let mut visible = false;
+                loop {
+                    let ev = tokio::select! {
+                        _ = cancel_token.cancelled() => return,
+                        ev = stream.next() => ev,
+                    };
+                    let Some(ev) = ev else { return };
+                    match &ev {
+                        Ok(ResponseEvent::OutputTextDelta(delta)) => {
+                            visible |= !delta.trim().is_empty();
+                        }
+                        // Tool calls are output too, even without any text.
+                        Ok(ResponseEvent::OutputItemDone(item)) => {
+                            visible |= has_visible_text(item)
+                                || matches!(
+                                    item,
+                                    ResponseItem::FunctionCall { .. }
+                                        | ResponseItem::LocalShellCall { .. }
+                                );
+                        }
+                        Ok(ResponseEvent::Completed { .. }) if !visible => break,
+                        _ => {}
+                    }
+                    if tx.send(ev).await.is_err() {
+                        return;
+                    }
+                }
+
+                warn!("response had no visible output; retrying with reasoning effort {to}");
+                if tx
+                    .send(Ok(ResponseEvent::ReasoningEscalated { from, to }))
+                    .await
+                    .is_err()
+                {
+                    return;
+                }
+                match client.stream_with_effort(&prompt, to).await {
+                    Ok(retry) => forward_events(retry, &tx, &cancel_token).await,
+                    Err(e) => {
+                        let _ = tx.send(Err(e)).await;
+                    }
+                }
+            }
+        });
+
+        ResponseStream {
+            rx_event: rx,
+            cancel_token,
+        }
+    }
+
+    async fn stream_with_effort(
+        &self,
+        prompt: &Prompt,
+        effort: ReasoningEffortConfig,
+    ) -> Result<ResponseStream> {
+        let mut fallbacks = self.config.provider_fallback.iter().filter_map(|id| {
+            let provider = self.config.model_providers.get(id);
+            if provider.is_none() {
+                warn!("ignoring unknown provider `{id}` in provider_fallback");
+            }
+            provider.map(|provider| (id, provider))
+        });
+
+        let mut provider_id = self.config.model_provider_id.clone();
+        let mut provider = &self.provider;
+        let mut failovers = Vec::new();
+        loop {
+            match self.stream_with_provider(prompt, provider, effort).await {
+                Ok(stream) if failovers.is_empty() => return Ok(stream),
+                Ok(stream) => return Ok(prepend_events(stream, failovers)),
+                Err(e) if is_failover_error(&e) => {
+                    let Some((next_id, next_provider)) = fallbacks.next() else {
+                        return Err(e);
+                    };
+                    warn!("provider `{provider_id}` failed ({e}); falling back to `{next_id}`");
+                    failovers.push(ResponseEvent::ProviderFailover {
+                        from: std::mem::replace(&mut provider_id, next_id.clone()),
+                        to: next_id.clone(),
+                    });
+                    provider = next_provider;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    async fn stream_with_provider(
+        &self,
+        prompt: &Prompt,
+        provider: &ModelProviderInfo,
+        effort: ReasoningEffortConfig,
+    ) -> Result<ResponseStream> {
+        match provider.wire_api {
+            WireApi::Responses => self.stream_responses(prompt, provider, effort).await,
             WireApi::Chat => {
                 // Create the raw streaming connection first.
                 let response_stream = stream_chat_completions(
                     prompt,
-                    &self.config.model,
+                    prompt.model(&self.config.model),
                     &self.client,
-                    &self.provider,
+                    provider,
                 )
                 .await?;
 
@@ -90,80 +245,135 @@ impl ModelClient {
                 // Bridge the aggregated stream back into a standard
                 // `ResponseStream` by forwarding events through a channel.
                 let (tx, rx) = mpsc::channel::<Result<ResponseEvent>>(16);
-
-                tokio::spawn(async move {
-                    use futures::StreamExt;
-                    while let Some(ev) = aggregated.next().await {
-                        // Exit early if receiver hung up.
-                        if tx.send(ev).await.is_err() {
-                            break;
+                let cancel_token = CancellationToken::new();
+
+                tokio::spawn({
+                    let cancel_token = cancel_token.clone();
+                    async move {
+                        use futures::StreamExt;
+                        loop {
+                            // Returning drops `aggregated`, which in turn
+                            // cancels the underlying chat completions stream.
+                            let ev = tokio::select! {
+                                _ = cancel_token.cancelled() => break,
+                                ev = aggregated.next() => ev,
+                            };
+                            let Some(ev) = ev else { break };
+                            // Exit early if receiver hung up.
+                            if tx.send(ev).await.is_err() {
+                                break;
+                            }
                         }
                     }
                 });
 
-                Ok(ResponseStream { rx_event: rx })
+                Ok(ResponseStream {
+                    rx_event: rx,
+                    cancel_token,
+                })
             }
         }
     }
 
     /// Implementation for the OpenAI *Responses* experimental API.
-    async fn stream_responses(&self, prompt: &Prompt) -> Result<ResponseStream> {
+    async fn stream_responses(
+        &self,
+        prompt: &Prompt,
+        provider: &ModelProviderInfo,
+        effort: ReasoningEffortConfig,
+    ) -> Result<ResponseStream> {
         if let Some(path) = &*CODEX_RS_SSE_FIXTURE {
             // short circuit for tests
             warn!(path, "Streaming from fixture");
-            return stream_from_fixture(path, self.provider.clone()).await;
+            return stream_from_fixture(path, provider.clone()).await;
         }
 
-        let full_instructions = prompt.get_full_instructions(&self.config.model);
-        let tools_json = create_tools_json_for_responses_api(prompt, &self.config.model)?;
-        let reasoning = create_reasoning_param_for_request(&self.config, self.effort, self.summary);
+        let model = prompt.model(&self.config.model);
+        let tools = create_tools_for_responses_api(prompt, model)?;
+        let reasoning =
+            create_reasoning_param_for_request(&self.config, model, effort, self.summary);
+        let full_instructions = with_reasoning_summary_language(
+            prompt.get_full_instructions(model),
+            reasoning.as_ref(),
+            self.config.model_reasoning_summary_language.as_deref(),
+        );
+        let include = prompt.include_strings_for_request(
+            reasoning.as_ref(),
+            self.config.strict_reasoning_include,
+        )?;
         let payload = ResponsesApiRequest {
-            model: &self.config.model,
+            model,
             instructions: &full_instructions,
             input: &prompt.input,
-            tools: &tools_json,
+            tools: &tools,
             tool_choice: "auto",
-            parallel_tool_calls: false,
+            parallel_tool_calls: prompt.parallel_tool_calls(),
             reasoning,
             previous_response_id: prompt.prev_id.clone(),
             store: prompt.store,
             // TODO: make this configurable
             stream: true,
+            include,
+            stop: prompt.stop_sequences(provider.max_stop_sequences())?,
         };
 
         trace!(
             "POST to {}: {}",
-            self.provider.get_full_url(),
+            provider.get_full_url(),
             serde_json::to_string(&payload)?
         );
 
         let mut attempt = 0;
-        let max_retries = self.provider.request_max_retries();
+        let max_retries = provider.request_max_retries();
         loop {
             attempt += 1;
 
-            let req_builder = self
-                .provider
+            let req_builder = provider
                 .create_request_builder(&self.client)?
                 .header("OpenAI-Beta", "responses=experimental")
                 .header("session_id", self.session_id.to_string())
                 .header(reqwest::header::ACCEPT, "text/event-stream")
                 .json(&payload);
 
-            let res = req_builder.send().await;
+            let span = create_llm_request_span(&prompt.trace, model, attempt);
+            span.in_scope(|| record_retry_count(u32::try_from(attempt - 1).unwrap_or(u32::MAX)));
+            let started = Instant::now();
+            let res = req_builder.send().instrument(span.clone()).await;
+            if let Ok(resp) = &res {
+                span.in_scope(|| record_llm_response(resp.status().as_u16(), started.elapsed()));
+            }
             match res {
                 Ok(resp) if resp.status().is_success() => {
                     let (tx_event, rx_event) = mpsc::channel::<Result<ResponseEvent>>(1600);
+                    let cancel_token = CancellationToken::new();
+
+                    let headers = capture_response_headers(
+                        resp.headers(),
+                        &self.config.response_header_allowlist,
+                    );
+                    if !headers.is_empty() {
+                        span.in_scope(|| record_rate_limits(&headers));
+                        let _ = tx_event
+                            .send(Ok(ResponseEvent::ResponseHeaders(headers)))
+                            .await;
+                    }
 
                     // spawn task to process SSE
                     let stream = resp.bytes_stream().map_err(CodexErr::Reqwest);
-                    tokio::spawn(process_sse(
-                        stream,
-                        tx_event,
-                        self.provider.stream_idle_timeout(),
-                    ));
-
-                    return Ok(ResponseStream { rx_event });
+                    tokio::spawn(
+                        process_sse(
+                            stream,
+                            tx_event,
+                            provider.stream_idle_timeout(),
+                            cancel_token.clone(),
+                        )
+                        .instrument(span),
+                    );
+
+                    return Ok(ResponseStream {
+                        rx_event,
+                        cancel_token,
+                    });
                 }
                 Ok(res) => {
                     let status = res.status();
@@ -177,11 +387,15 @@ impl ModelClient {
                     if !(status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()) {
                         // Surface the error body to callers. Use `unwrap_or_default` per Clippy.
                         let body = res.text().await.unwrap_or_default();
-                        return Err(CodexErr::UnexpectedStatus(status, body));
+                        let err = CodexErr::UnexpectedStatus(status, body);
+                        span.in_scope(|| record_error(&err));
+                        return Err(err);
                     }
 
                     if attempt > max_retries {
-                        return Err(CodexErr::RetryLimit(status));
+                        let err = CodexErr::RetryLimit(status);
+                        span.in_scope(|| record_error(&err));
+                        return Err(err);
                     }
 
                     // Pull out Retry‑After header if present.
@@ -194,30 +408,115 @@ impl ModelClient {
                     let delay = retry_after_secs
                         .map(|s| Duration::from_millis(s * 1_000))
                         .unwrap_or_else(|| backoff(attempt));
+                    crate::telemetry::record_llm_retry();
                     tokio::time::sleep(delay).await;
                 }
                 Err(e) => {
                     if attempt > max_retries {
+                        span.in_scope(|| record_error(&e));
                         return Err(e.into());
                     }
                     let delay = backoff(attempt);
+                    crate::telemetry::record_llm_retry();
                     tokio::time::sleep(delay).await;
                 }
             }
         }
     }
 
+    /// Id of the session this client sends requests for; also the id of the
+    /// session's rollout.
+    pub fn session_id(&self) -> Uuid {
+        self.session_id
+    }
+
+    /// The configured model, used unless a prompt overrides it.
+    pub fn model(&self) -> &str {
+        &self.config.model
+    }
+
     pub fn get_provider(&self) -> ModelProviderInfo {
         self.provider.clone()
     }
 }
 
+/// Errors after which another provider may succeed where this one did not:
+/// connectivity problems and server errors that outlasted the retries.
+fn is_failover_error(err: &CodexErr) -> bool {
+    match err {
+        CodexErr::RetryLimit(status) => status.is_server_error(),
+        CodexErr::Reqwest(e) => e.is_connect() || e.is_timeout(),
+        _ => false,
+    }
+}
+
+/// Picks the headers named in `allowlist` out of `headers`. Header names are
+/// case-insensitive, so keys are normalized to lowercase; values that are not
+/// valid UTF-8 are skipped.
+fn capture_response_headers(
+    headers: &reqwest::header::HeaderMap,
+    allowlist: &[String],
+) -> HashMap<String, String> {
+    allowlist
+        .iter()
+        .filter_map(|name| {
+            let value = headers.get(name.as_str())?.to_str().ok()?;
+            Some((name.to_ascii_lowercase(), value.to_string()))
+        })
+        .collect()
+}
+
+/// Returns a stream that yields `events` before everything from `stream`.
+fn prepend_events(stream: ResponseStream, events: Vec<ResponseEvent>) -> ResponseStream {
+    let (tx, rx) = mpsc::channel::<Result<ResponseEvent>>(16);
+    // The inner stream cancels its own token when dropped, so the outer
+    // stream needs a separate one to avoid cutting off forwarded events.
+    let cancel_token = CancellationToken::new();
+
+    tokio::spawn({
+        let cancel_token = cancel_token.clone();
+        async move {
+            for event in events {
+                if tx.send(Ok(event)).await.is_err() {
+                    return;
+                }
+            }
+            forward_events(stream, &tx, &cancel_token).await;
+        }
+    });
+
+    ResponseStream {
+        rx_event: rx,
+        cancel_token,
+    }
+}
+
+/// Forwards every event of `stream` to `tx` until the stream ends, the
+/// receiver hangs up or `cancel_token` is cancelled.
+async fn forward_events(
+    mut stream: ResponseStream,
+    tx: &mpsc::Sender<Result<ResponseEvent>>,
+    cancel_token: &CancellationToken,
+) {
+    loop {
+        let ev = tokio::select! {
+            _ = cancel_token.cancelled() => return,
+            ev = stream.next() => ev,
+        };
+        let Some(ev) = ev else { return };
+        if tx.send(ev).await.is_err() {
+            return;
+        }
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 struct SseEvent {
     #[serde(rename = "type")]
     kind: String,
     response: Option<Value>,
     item: Option<Value>,
+    item_id: Option<String>,
     delta: Option<String>,
 }
 
@@ -265,6 +564,7 @@ async fn process_sse<S>(
     stream: S,
     tx_event: mpsc::Sender<Result<ResponseEvent>>,
     idle_timeout: Duration,
+    cancel_token: CancellationToken,
 ) where
     S: Stream<Item = Result<Bytes>> + Unpin,
 {
@@ -275,11 +575,19 @@ async fn process_sse<S>(
     let mut response_completed: Option<ResponseCompleted> = None;
 
     loop {
-        let sse = match timeout(idle_timeout, stream.next()).await {
+        let next = tokio::select! {
+            _ = cancel_token.cancelled() => {
+                trace!("response stream cancelled");
+                return;
+            }
+            next = timeout(idle_timeout, stream.next()) => next,
+        };
+        let sse = match next {
             Ok(Some(Ok(sse))) => sse,
             Ok(Some(Err(e))) => {
                 debug!("SSE Error: {e:#}");
                 let event = CodexErr::Stream(e.to_string());
+                record_error(&event);
                 let _ = tx_event.send(Err(event)).await;
                 return;
             }
@@ -289,26 +597,29 @@ async fn process_sse<S>(
                         id: response_id,
                         usage,
                     }) => {
+                        let token_usage: Option<TokenUsage> = usage.map(Into::into);
+                        if let Some(usage) = &token_usage {
+                            record_token_usage(usage);
+                        }
                         let event = ResponseEvent::Completed {
                             response_id,
-                            token_usage: usage.map(Into::into),
+                            token_usage,
                         };
                         let _ = tx_event.send(Ok(event)).await;
                     }
                     None => {
-                        let _ = tx_event
-                            .send(Err(CodexErr::Stream(
-                                "stream closed before response.completed".into(),
-                            )))
-                            .await;
+                        let event =
+                            CodexErr::Stream("stream closed before response.completed".into());
+                        record_error(&event);
+                        let _ = tx_event.send(Err(event)).await;
                     }
                 }
                 return;
             }
             Err(_) => {
-                let _ = tx_event
-                    .send(Err(CodexErr::Stream("idle timeout waiting for SSE".into())))
-                    .await;
+                let event = CodexErr::Stream("idle timeout waiting for SSE".into());
+                record_error(&event);
+                let _ = tx_event.send(Err(event)).await;
                 return;
             }
         };
@@ -363,7 +674,10 @@ async fn process_sse<S>(
             }
             "response.reasoning_summary_text.delta" => {
                 if let Some(delta) = event.delta {
-                    let event = ResponseEvent::ReasoningSummaryDelta(delta);
+                    let event = ResponseEvent::ReasoningSummaryDelta {
+                        item_id: event.item_id.unwrap_or_default(),
+                        delta,
+                    };
                     if tx_event.send(Ok(event)).await.is_err() {
                         return;
                     }
@@ -409,6 +723,7 @@ async fn stream_from_fixture(
     provider: ModelProviderInfo,
 ) -> Result<ResponseStream> {
     let (tx_event, rx_event) = mpsc::channel::<Result<ResponseEvent>>(1600);
+    let cancel_token = CancellationToken::new();
     let f = std::fs::File::open(path.as_ref())?;
     let lines = std::io::BufReader::new(f).lines();
 
@@ -425,8 +740,12 @@ async fn stream_from_fixture(
         stream,
         tx_event,
         provider.stream_idle_timeout(),
+        cancel_token.clone(),
     ));
-    Ok(ResponseStream { rx_event })
+    Ok(ResponseStream {
+        rx_event,
+        cancel_token,
+    })
 }
 
 #[cfg(test)]
@@ -434,11 +753,41 @@ mod tests {
     #![allow(clippy::expect_used, clippy::unwrap_used)]
 
     use super::*;
+    use crate::models::ReasoningItemReasoningSummary;
     use serde_json::json;
     use tokio::sync::mpsc;
     use tokio_test::io::Builder as IoBuilder;
     use tokio_util::io::ReaderStream;
 
+    #[test]
+    fn captures_allowlisted_response_headers() {
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert("x-ratelimit-remaining-requests", "59".parse().unwrap());
+        headers.insert("x-ratelimit-remaining-tokens", "149984".parse().unwrap());
+        headers.insert("x-request-id", "req_123".parse().unwrap());
+        let allowlist = vec![
+            "X-RateLimit-Remaining-Requests".to_string(),
+            "x-ratelimit-remaining-tokens".to_string(),
+            "x-ratelimit-reset-tokens".to_string(),
+        ];
+
+        let captured = capture_response_headers(&headers, &allowlist);
+
+        assert_eq!(
+            captured,
+            HashMap::from([
+                (
+                    "x-ratelimit-remaining-requests".to_string(),
+                    "59".to_string()
+                ),
+                (
+                    "x-ratelimit-remaining-tokens".to_string(),
+                    "149984".to_string()
+                ),
+            ])
+        );
+    }
+
     // ────────────────────────────
     // Helpers
     // ────────────────────────────
@@ -457,7 +806,12 @@ mod tests {
         let reader = builder.build();
         let stream = ReaderStream::new(reader).map_err(CodexErr::Io);
         let (tx, mut rx) = mpsc::channel::<Result<ResponseEvent>>(16);
-        tokio::spawn(process_sse(stream, tx, provider.stream_idle_timeout()));
+        tokio::spawn(process_sse(
+            stream,
+            tx,
+            provider.stream_idle_timeout(),
+            CancellationToken::new(),
+        ));
 
         let mut events = Vec::new();
         while let Some(ev) = rx.recv().await {
@@ -487,7 +841,12 @@ mod tests {
 
         let (tx, mut rx) = mpsc::channel::<Result<ResponseEvent>>(8);
         let stream = ReaderStream::new(std::io::Cursor::new(body)).map_err(CodexErr::Io);
-        tokio::spawn(process_sse(stream, tx, provider.stream_idle_timeout()));
+        tokio::spawn(process_sse(
+            stream,
+            tx,
+            provider.stream_idle_timeout(),
+            CancellationToken::new(),
+        ));
 
         let mut out = Vec::new();
         while let Some(ev) = rx.recv().await {
@@ -544,6 +903,7 @@ mod tests {
             request_max_retries: Some(0),
             stream_max_retries: Some(0),
             stream_idle_timeout_ms: Some(1000),
+            max_stop_sequences: None,
         };
 
         let events = collect_events(
@@ -603,6 +963,7 @@ mod tests {
             request_max_retries: Some(0),
             stream_max_retries: Some(0),
             stream_idle_timeout_ms: Some(1000),
+            max_stop_sequences: None,
         };
 
         let events = collect_events(&[sse1.as_bytes()], provider).await;
@@ -619,6 +980,115 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn cancel_stops_events_mid_stream() {
+        let item = json!({
+            "type": "response.output_item.done",
+            "item": {
+                "type": "message",
+                "role": "assistant",
+                "content": [{"type": "output_text", "text": "Hello"}]
+            }
+        })
+        .to_string();
+        let sse = format!("event: response.output_item.done\ndata: {item}\n\n");
+
+        // Two items are available immediately, after which the server goes
+        // quiet without ever sending `response.completed`.
+        let body = futures::stream::iter(vec![Ok(Bytes::from(sse.clone())), Ok(Bytes::from(sse))])
+            .chain(futures::stream::pending());
+
+        let (tx, rx) = mpsc::channel::<Result<ResponseEvent>>(16);
+        let cancel_token = CancellationToken::new();
+        let producer = tokio::spawn(process_sse(
+            body,
+            tx,
+            Duration::from_secs(60),
+            cancel_token.clone(),
+        ));
+        let mut stream = ResponseStream {
+            rx_event: rx,
+            cancel_token,
+        };
+
+        let first = stream.next().await;
+        assert!(matches!(first, Some(Ok(ResponseEvent::OutputItemDone(_)))));
+
+        stream.cancel();
+        assert!(stream.next().await.is_none());
+        assert!(stream.next().await.is_none());
+
+        // The producer must stop even though the body never ends.
+        tokio::time::timeout(Duration::from_secs(1), producer)
+            .await
+            .expect("producer should stop after cancel")
+            .unwrap();
+    }
+
+    /// Concatenating the reasoning summary deltas of one item yields the
+    /// summary text of the finished item, even when items interleave.
+    #[tokio::test]
+    async fn reasoning_summary_deltas_accumulate_per_item() {
+        let delta = |item_id: &str, delta: &str| {
+            json!({
+                "type": "response.reasoning_summary_text.delta",
+                "item_id": item_id,
+                "output_index": 0,
+                "summary_index": 0,
+                "delta": delta,
+            })
+        };
+        let events = vec![
+            delta("rs_1", "Checking "),
+            delta("rs_2", "Running "),
+            delta("rs_1", "the tests."),
+            json!({
+                "type": "response.output_item.done",
+                "item": {
+                    "type": "reasoning",
+                    "id": "rs_1",
+                    "summary": [{"type": "summary_text", "text": "Checking the tests."}]
+                }
+            }),
+            json!({"type": "response.completed", "response": {"id": "resp1"}}),
+        ];
+        let provider = ModelProviderInfo {
+            name: "test".to_string(),
+            base_url: "https://test.com".to_string(),
+            env_key: Some("TEST_API_KEY".to_string()),
+            env_key_instructions: None,
+            wire_api: WireApi::Responses,
+            query_params: None,
+            http_headers: None,
+            env_http_headers: None,
+            request_max_retries: Some(0),
+            stream_max_retries: Some(0),
+            stream_idle_timeout_ms: Some(1000),
+            max_stop_sequences: None,
+        };
+
+        let mut summaries: HashMap<String, String> = HashMap::new();
+        let mut done = None;
+        for event in run_sse(events, provider).await {
+            match event {
+                ResponseEvent::ReasoningSummaryDelta { item_id, delta } => {
+                    summaries.entry(item_id).or_default().push_str(&delta);
+                }
+                ResponseEvent::OutputItemDone(ResponseItem::Reasoning { id, summary }) => {
+                    done = Some((id, summary));
+                }
+                _ => {}
+            }
+        }
+
+        let (id, summary) = done.expect("reasoning item");
+        let [ReasoningItemReasoningSummary::SummaryText { text }] = summary.as_slice() else {
+            panic!("unexpected summary: {summary:?}");
+        };
+        assert_eq!(&summaries[&id], text);
+        assert_eq!(summaries["rs_2"], "Running ");
+    }
+
     // ────────────────────────────
     // Table-driven test from `main`
     // ────────────────────────────
@@ -705,6 +1175,7 @@ mod tests {
                 request_max_retries: Some(0),
                 stream_max_retries: Some(0),
                 stream_idle_timeout_ms: Some(1000),
+                max_stop_sequences: None,
             };
 
             let out = run_sse(evs, provider).await;
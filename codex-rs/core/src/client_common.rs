@@ -1,22 +1,152 @@
 use crate::config_types::ReasoningEffort as ReasoningEffortConfig;
 use crate::config_types::ReasoningSummary as ReasoningSummaryConfig;
+use crate::conversation_tracing::TraceContext;
+use crate::error::CodexErr;
 use crate::error::Result;
+use crate::models::ContentItem;
 use crate::models::ResponseItem;
+use crate::openai_tools::Tool;
 use crate::protocol::TokenUsage;
+use crate::rollout::RolloutRecorder;
+use crate::util::normalize_instructions;
 use codex_apply_patch::APPLY_PATCH_TOOL_INSTRUCTIONS;
 use futures::Stream;
+use futures::StreamExt;
 use serde::Serialize;
 use std::borrow::Cow;
 use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::collections::hash_map::DefaultHasher;
+use std::future::Future;
+use std::hash::Hash;
+use std::hash::Hasher;
 use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::PoisonError;
+use std::sync::RwLock;
 use std::task::Context;
 use std::task::Poll;
 use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+use tracing::warn;
 
 /// The `instructions` field in the payload sent to a model should always start
 /// with this content.
 const BASE_INSTRUCTIONS: &str = include_str!("../prompt.md");
 
+/// Extra instructions appended after the user instructions for the models
+/// they apply to.
+struct InstructionFragment {
+    /// Whether the fragment applies to a model slug.
+    applies_to: fn(&str) -> bool,
+    text: &'static str,
+}
+
+/// Model-specific fragments, in the order they are appended. Every matching
+/// rule contributes, so a model can pick up several fragments.
+const MODEL_INSTRUCTION_FRAGMENTS: &[InstructionFragment] = &[InstructionFragment {
+    applies_to: |model| model.starts_with("gpt-4.1"),
+    text: APPLY_PATCH_TOOL_INSTRUCTIONS,
+}];
+
+/// The text of every rule in `rules` that applies to `model`, in rule order.
+fn instruction_fragments<'a>(
+    rules: &'a [InstructionFragment],
+    model: &'a str,
+) -> impl Iterator<Item = &'a str> {
+    rules
+        .iter()
+        .filter(move |rule| (rule.applies_to)(model))
+        .map(|rule| rule.text)
+}
+
+/// What one set of full instructions is assembled from. The model slug
+/// determines which fragments are appended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct FullInstructionsKey<'a> {
+    model: &'a str,
+    base_instructions: Option<&'a str>,
+    user_instructions: Option<&'a str>,
+}
+
+impl FullInstructionsKey<'_> {
+    fn hash_value(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+/// An assembled set of instructions and the inputs it was assembled from.
+struct CachedInstructions {
+    hash: u64,
+    model: String,
+    base_instructions: Option<String>,
+    user_instructions: Option<String>,
+    full: Arc<str>,
+}
+
+impl CachedInstructions {
+    fn matches(&self, hash: u64, key: &FullInstructionsKey<'_>) -> bool {
+        // The hash only rules entries out cheaply; the texts decide.
+        self.hash == hash
+            && self.model == key.model
+            && self.base_instructions.as_deref() == key.base_instructions
+            && self.user_instructions.as_deref() == key.user_instructions
+    }
+}
+
+/// Most recently assembled instructions, oldest first. Bounded so that a
+/// long-running process whose instructions keep changing (e.g. edits to
+/// `base_instructions_file`) does not accumulate every version.
+struct InstructionsCache {
+    entries: VecDeque<CachedInstructions>,
+    capacity: usize,
+}
+
+impl InstructionsCache {
+    const fn new(capacity: usize) -> Self {
+        Self {
+            entries: VecDeque::new(),
+            capacity,
+        }
+    }
+
+    fn get(&self, hash: u64, key: &FullInstructionsKey<'_>) -> Option<Arc<str>> {
+        self.entries
+            .iter()
+            .find(|entry| entry.matches(hash, key))
+            .map(|entry| Arc::clone(&entry.full))
+    }
+
+    /// Caches `full` for `key`, evicting the oldest entry if the cache is
+    /// full, and returns the cached instructions for `key`.
+    fn insert(&mut self, hash: u64, key: &FullInstructionsKey<'_>, full: Arc<str>) -> Arc<str> {
+        if let Some(cached) = self.get(hash, key) {
+            return cached;
+        }
+        if self.entries.len() >= self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(CachedInstructions {
+            hash,
+            model: key.model.to_string(),
+            base_instructions: key.base_instructions.map(str::to_string),
+            user_instructions: key.user_instructions.map(str::to_string),
+            full: Arc::clone(&full),
+        });
+        full
+    }
+}
+
+/// Number of instruction sets kept by [`FULL_INSTRUCTIONS_CACHE`].
+const FULL_INSTRUCTIONS_CACHE_ENTRIES: usize = 16;
+
+/// Assembled instructions are immutable for a given key, so they are cached
+/// process-wide instead of being rebuilt and re-joined on every turn.
+static FULL_INSTRUCTIONS_CACHE: RwLock<InstructionsCache> =
+    RwLock::new(InstructionsCache::new(FULL_INSTRUCTIONS_CACHE_ENTRIES));
+
 /// API request payload for a single model turn.
 #[derive(Default, Debug, Clone)]
 pub struct Prompt {
@@ -30,23 +160,230 @@ pub struct Prompt {
     /// Whether to store response on server side (disable_response_storage = !store).
     pub store: bool,
 
+    /// Tools to offer the model in addition to the built-in ones. They are
+    /// sent ahead of `extra_tools`; any the model does not support are
+    /// dropped.
+    pub tools: Vec<Tool>,
+
     /// Additional tools sourced from external MCP servers. Note each key is
     /// the "fully qualified" tool name (i.e., prefixed with the server name),
-    /// which should be reported to the model in place of Tool::name.
+    /// which should be reported to the model in place of Tool::name. Tools
+    /// whose name is already taken by another tool are not sent.
     pub extra_tools: HashMap<String, mcp_types::Tool>,
+
+    /// Additional output data to request from the Responses API.
+    pub includes: Vec<IncludeOption>,
+
+    /// Sequences at which the model should stop generating further tokens.
+    pub stop: Option<Vec<String>>,
+
+    /// Normalize line endings and trailing whitespace in `user_instructions`
+    /// before they are appended to the base instructions.
+    pub normalize_user_instructions: bool,
+
+    /// Whether the model may make several tool calls in one response. `None`
+    /// keeps the default of not requesting parallel calls.
+    pub parallel_tool_calls: Option<bool>,
+
+    /// Model to use for this turn only, e.g. a pinned snapshot. Takes
+    /// precedence over `Config::model` when set.
+    pub model_override: Option<String>,
+
+    /// Replaces the built-in base instructions when set, e.g. with the
+    /// contents of `Config::base_instructions_file`.
+    pub base_instructions_override: Option<String>,
+
+    /// Session and turn attached to the spans of this request.
+    pub(crate) trace: TraceContext,
+}
+
+/// Known values for the `include` field of a Responses API request. Each one
+/// asks the server to return an extra piece of data that is omitted by
+/// default.
+/// See https://platform.openai.com/docs/api-reference/responses/create#responses-create-include
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IncludeOption {
+    /// Encrypted reasoning tokens, which let reasoning items be passed back
+    /// to the model when responses are not stored (e.g. with Zero Data
+    /// Retention).
+    ReasoningEncryptedContent,
+    FileSearchCallResults,
+    MessageInputImageUrl,
+    ComputerCallOutputImageUrl,
+    CodeInterpreterCallOutputs,
+}
+
+impl IncludeOption {
+    /// The string the Responses API expects for this option.
+    pub fn as_wire_str(self) -> &'static str {
+        match self {
+            IncludeOption::ReasoningEncryptedContent => "reasoning.encrypted_content",
+            IncludeOption::FileSearchCallResults => "file_search_call.results",
+            IncludeOption::MessageInputImageUrl => "message.input_image.image_url",
+            IncludeOption::ComputerCallOutputImageUrl => "computer_call_output.output.image_url",
+            IncludeOption::CodeInterpreterCallOutputs => "code_interpreter_call.outputs",
+        }
+    }
 }
 
 impl Prompt {
-    pub(crate) fn get_full_instructions(&self, model: &str) -> Cow<'_, str> {
-        let mut sections: Vec<&str> = vec![BASE_INSTRUCTIONS];
-        if let Some(ref user) = self.user_instructions {
-            sections.push(user);
+    /// Preset for reasoning without server-side storage, for use as the base
+    /// of a struct update (`Prompt { input, ..Prompt::stateless_reasoning() }`).
+    /// It differs from the default prompt in exactly two ways:
+    ///
+    /// - `store` is `false`, so the server keeps no copy of the response;
+    /// - `includes` requests [`IncludeOption::ReasoningEncryptedContent`], so
+    ///   reasoning items come back in a form that can be sent again as input.
+    ///
+    /// `prev_id` is left unset because it is meaningless without storage. The
+    /// reasoning parameters themselves still come from the model and config,
+    /// and with this preset pass `strict_reasoning_include`.
+    pub fn stateless_reasoning() -> Self {
+        Self {
+            store: false,
+            includes: vec![IncludeOption::ReasoningEncryptedContent],
+            ..Default::default()
         }
-        if model.starts_with("gpt-4.1") {
-            sections.push(APPLY_PATCH_TOOL_INSTRUCTIONS);
+    }
+
+    /// Merges runs of adjacent messages from the same role (and name) into
+    /// one message holding all of their content, in order. Every other item,
+    /// in particular a function call or its output, ends the run, so
+    /// messages on either side of a tool call are never merged.
+    pub fn normalize_input(&mut self) {
+        let mut merged: Vec<ResponseItem> = Vec::with_capacity(self.input.len());
+        for item in std::mem::take(&mut self.input) {
+            match (merged.last_mut(), item) {
+                (
+                    Some(ResponseItem::Message {
+                        role: last_role,
+                        content: last_content,
+                        name: last_name,
+                    }),
+                    ResponseItem::Message {
+                        role,
+                        content,
+                        name,
+                    },
+                ) if *last_role == role && *last_name == name => last_content.extend(content),
+                (_, item) => merged.push(item),
+            }
         }
-        Cow::Owned(sections.join("\n"))
+        self.input = merged;
+    }
+
+    /// The model this prompt should be sent to: the override if present,
+    /// otherwise `default` (normally `Config::model`).
+    pub(crate) fn model<'a>(&'a self, default: &'a str) -> &'a str {
+        self.model_override.as_deref().unwrap_or(default)
     }
+
+    /// Value for [`ResponsesApiRequest::parallel_tool_calls`].
+    pub(crate) fn parallel_tool_calls(&self) -> bool {
+        self.parallel_tool_calls.unwrap_or(false)
+    }
+
+    /// Wire representation of [`Prompt::includes`] for the request payload.
+    pub(crate) fn include_strings(&self) -> Vec<String> {
+        self.includes
+            .iter()
+            .map(|include| include.as_wire_str().to_string())
+            .collect()
+    }
+
+    /// Wire `include` values for a request asking for `reasoning`. Without
+    /// server-side storage, reasoning items can only be passed back to the
+    /// model if their encrypted content is returned, so when `store` is off
+    /// and [`IncludeOption::ReasoningEncryptedContent`] is missing it is added
+    /// or, with `strict`, the request is refused.
+    pub(crate) fn include_strings_for_request(
+        &self,
+        reasoning: Option<&Reasoning>,
+        strict: bool,
+    ) -> Result<Vec<String>> {
+        let mut include = self.include_strings();
+        let encrypted = IncludeOption::ReasoningEncryptedContent.as_wire_str();
+        if reasoning.is_some() && !self.store && !include.iter().any(|i| i == encrypted) {
+            if strict {
+                return Err(CodexErr::MissingReasoningInclude);
+            }
+            include.push(encrypted.to_string());
+        }
+        Ok(include)
+    }
+
+    /// Stop sequences to send with the request, or `None` if there are none.
+    /// More than the provider's `max` is refused up front rather than sending
+    /// a request the provider would reject, or silently dropping some.
+    pub(crate) fn stop_sequences(&self, max: usize) -> Result<Option<Vec<String>>> {
+        let Some(stop) = self.stop.as_ref().filter(|stop| !stop.is_empty()) else {
+            return Ok(None);
+        };
+        if stop.len() > max {
+            return Err(CodexErr::TooManyStopSequences {
+                requested: stop.len(),
+                max,
+            });
+        }
+        Ok(Some(stop.clone()))
+    }
+
+    pub(crate) fn get_full_instructions(&self, model: &str) -> Arc<str> {
+        let user_instructions = self.user_instructions.as_deref().map(|user| {
+            if self.normalize_user_instructions {
+                Cow::Owned(normalize_instructions(user))
+            } else {
+                Cow::Borrowed(user)
+            }
+        });
+        let key = FullInstructionsKey {
+            model,
+            base_instructions: self.base_instructions_override.as_deref(),
+            user_instructions: user_instructions.as_deref(),
+        };
+        let hash = key.hash_value();
+        let cached = FULL_INSTRUCTIONS_CACHE
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(hash, &key);
+        if let Some(cached) = cached {
+            return cached;
+        }
+
+        let base = self
+            .base_instructions_override
+            .as_deref()
+            .unwrap_or(BASE_INSTRUCTIONS);
+        let mut sections: Vec<&str> = vec![base];
+        if let Some(ref user) = user_instructions {
+            sections.push(user);
+        }
+        sections.extend(instruction_fragments(MODEL_INSTRUCTION_FRAGMENTS, model));
+        let full: Arc<str> = Arc::from(sections.join("\n"));
+        FULL_INSTRUCTIONS_CACHE
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(hash, &key, full)
+    }
+}
+
+/// Reads `Config::base_instructions_file`, if set. The error names the file so
+/// that a missing or unreadable prompt is easy to track down.
+pub(crate) async fn load_base_instructions(
+    config: &Config,
+) -> std::result::Result<Option<String>, String> {
+    let Some(path) = config.base_instructions_file.as_ref() else {
+        return Ok(None);
+    };
+    tokio::fs::read_to_string(path)
+        .await
+        .map(Some)
+        .map_err(|e| {
+            format!(
+                "failed to read base_instructions_file {}: {e}",
+                path.display()
+            )
+        })
 }
 
 #[derive(Debug)]
@@ -58,7 +395,32 @@ pub enum ResponseEvent {
         token_usage: Option<TokenUsage>,
     },
     OutputTextDelta(String),
-    ReasoningSummaryDelta(String),
+    /// Text appended to a reasoning summary. `item_id` is the id of the
+    /// [`ResponseItem::Reasoning`] that later arrives in `OutputItemDone`;
+    /// concatenating the deltas of one id in order yields that item's summary
+    /// text. This used to be a tuple variant carrying only the delta; code
+    /// that does not care about the item can match
+    /// `ReasoningSummaryDelta { delta, .. }`.
+    ReasoningSummaryDelta {
+        item_id: String,
+        delta: String,
+    },
+    /// The configured provider failed and the request was re-sent to the next
+    /// provider in `provider_fallback`. Both fields are provider ids.
+    ProviderFailover {
+        from: String,
+        to: String,
+    },
+    /// Provider response headers named in `response_header_allowlist`
+    /// (e.g. rate-limit headers), keyed by lowercase header name. Sent
+    /// before any other event of the response.
+    ResponseHeaders(HashMap<String, String>),
+    /// The response completed without visible output, so the same prompt was
+    /// re-issued at a higher reasoning effort (`escalate_reasoning_on_empty`).
+    ReasoningEscalated {
+        from: ReasoningEffortConfig,
+        to: ReasoningEffortConfig,
+    },
 }
 
 #[derive(Debug, Serialize)]
@@ -66,6 +428,8 @@ pub(crate) struct Reasoning {
     pub(crate) effort: OpenAiReasoningEffort,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub(crate) summary: Option<OpenAiReasoningSummary>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) max_reasoning_tokens: Option<u64>,
 }
 
 /// See https://platform.openai.com/docs/guides/reasoning?api-mode=responses#get-started-with-reasoning
@@ -122,7 +486,7 @@ pub(crate) struct ResponsesApiRequest<'a> {
     // we code defensively to avoid this case, but perhaps we should use a
     // separate enum for serialization.
     pub(crate) input: &'a Vec<ResponseItem>,
-    pub(crate) tools: &'a [serde_json::Value],
+    pub(crate) tools: &'a [Tool],
     pub(crate) tool_choice: &'static str,
     pub(crate) parallel_tool_calls: bool,
     pub(crate) reasoning: Option<Reasoning>,
@@ -131,28 +495,83 @@ pub(crate) struct ResponsesApiRequest<'a> {
     /// true when using the Responses API.
     pub(crate) store: bool,
     pub(crate) stream: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub(crate) include: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) stop: Option<Vec<String>>,
 }
 
 use crate::config::Config;
 
 pub(crate) fn create_reasoning_param_for_request(
     config: &Config,
+    model: &str,
     effort: ReasoningEffortConfig,
     summary: ReasoningSummaryConfig,
 ) -> Option<Reasoning> {
-    if model_supports_reasoning_summaries(config) {
+    if model_name_supports_reasoning_summaries(config, model) {
         let effort: Option<OpenAiReasoningEffort> = effort.into();
         let effort = effort?;
         Some(Reasoning {
             effort,
             summary: summary.into(),
+            max_reasoning_tokens: config.model_max_reasoning_tokens,
         })
     } else {
         None
     }
 }
 
+/// Appends an instruction asking for reasoning summaries in the configured
+/// `model_reasoning_summary_language`. The Responses API has no parameter for
+/// this, so it is best-effort: the model may still answer in English. Nothing
+/// is added unless a reasoning summary is actually being requested.
+pub(crate) fn with_reasoning_summary_language(
+    instructions: Arc<str>,
+    reasoning: Option<&Reasoning>,
+    language: Option<&str>,
+) -> Arc<str> {
+    match (reasoning.and_then(|r| r.summary.as_ref()), language) {
+        (Some(_), Some(language)) => Arc::from(format!(
+            "{instructions}\nWrite your reasoning summaries in {language}."
+        )),
+        _ => instructions,
+    }
+}
+
+/// The reasoning effort one step above `effort`, or `None` if it is already
+/// the highest (or reasoning is disabled).
+pub(crate) fn next_reasoning_effort(
+    effort: ReasoningEffortConfig,
+) -> Option<ReasoningEffortConfig> {
+    match effort {
+        ReasoningEffortConfig::Low => Some(ReasoningEffortConfig::Medium),
+        ReasoningEffortConfig::Medium => Some(ReasoningEffortConfig::High),
+        ReasoningEffortConfig::High | ReasoningEffortConfig::None => None,
+    }
+}
+
+/// Whether `item` is an assistant message with non-blank text, i.e. something
+/// the user would actually see.
+pub(crate) fn has_visible_text(item: &ResponseItem) -> bool {
+    match item {
+        ResponseItem::Message { role, content, .. } if role == "assistant" => {
+            content.iter().any(|c| match c {
+                ContentItem::OutputText { text } => !text.trim().is_empty(),
+                _ => false,
+            })
+        }
+        _ => false,
+    }
+}
+
 pub fn model_supports_reasoning_summaries(config: &Config) -> bool {
+    model_name_supports_reasoning_summaries(config, &config.model)
+}
+
+/// Like [`model_supports_reasoning_summaries`], but for `model` rather than
+/// `config.model`, e.g. when a [`Prompt`] overrides the model for one turn.
+pub(crate) fn model_name_supports_reasoning_summaries(config: &Config, model: &str) -> bool {
     // Currently, we hardcode this rule to decide whether to enable reasoning.
     // We expect reasoning to apply only to OpenAI models, but we do not want
     // users to have to mess with their config to disable reasoning for models
@@ -169,18 +588,843 @@ pub fn model_supports_reasoning_summaries(config: &Config) -> bool {
         return true;
     }
 
-    let model = &config.model;
     model.starts_with("o") || model.starts_with("codex")
 }
 
-pub(crate) struct ResponseStream {
+pub struct ResponseStream {
     pub(crate) rx_event: mpsc::Receiver<Result<ResponseEvent>>,
+    /// Shared with the task producing `rx_event` so that it can stop reading
+    /// from the network as soon as the stream is cancelled.
+    pub(crate) cancel_token: CancellationToken,
+}
+
+impl ResponseStream {
+    /// Aborts the in-flight turn.
+    ///
+    /// Ordering guarantees: any event already returned by `poll_next` stays
+    /// delivered, but once `cancel()` returns every subsequent poll yields
+    /// `None`, even if more events were buffered in the channel. The producer
+    /// task observes the same token and stops at its next await point, so the
+    /// underlying request is released promptly rather than when it drains.
+    pub fn cancel(&self) {
+        self.cancel_token.cancel();
+    }
+
+    /// Re-chunks `OutputTextDelta` events at sentence boundaries. See
+    /// [`SentenceChunks`].
+    pub fn sentence_chunks(self) -> SentenceChunks<Self> {
+        SentenceChunks {
+            inner: self,
+            buffer: String::new(),
+            ready: VecDeque::new(),
+            done: false,
+        }
+    }
+}
+
+impl Drop for ResponseStream {
+    fn drop(&mut self) {
+        // Dropping the stream should not leave the producer task reading a
+        // response nobody will consume.
+        self.cancel();
+    }
 }
 
 impl Stream for ResponseStream {
     type Item = Result<ResponseEvent>;
 
     fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if self.cancel_token.is_cancelled() {
+            return Poll::Ready(None);
+        }
         self.rx_event.poll_recv(cx)
     }
 }
+
+/// Characters after which buffered text is released as a chunk.
+const SENTENCE_BOUNDARIES: [char; 4] = ['.', '!', '?', '\n'];
+
+/// Stream adapter that buffers the token-sized `OutputTextDelta` fragments
+/// produced by the model and re-emits them as one `OutputTextDelta` per
+/// sentence, which is friendlier for text-to-speech and incremental
+/// rendering.
+///
+/// A chunk ends right after a sentence boundary (`.`, `!`, `?` or a newline).
+/// Any text still buffered is flushed as a final chunk before the next
+/// non-delta event (e.g. `OutputItemDone` or `Completed`) and when the inner
+/// stream ends, so no text is ever dropped. All other events pass through
+/// unchanged and in order.
+pub struct SentenceChunks<S> {
+    inner: S,
+    buffer: String,
+    ready: VecDeque<Result<ResponseEvent>>,
+    done: bool,
+}
+
+impl<S> SentenceChunks<S> {
+    /// Moves every complete sentence from `buffer` to `ready`.
+    fn split_sentences(&mut self) {
+        while let Some(idx) = self.buffer.find(SENTENCE_BOUNDARIES) {
+            // All boundaries are single-byte ASCII characters.
+            let rest = self.buffer.split_off(idx + 1);
+            let sentence = std::mem::replace(&mut self.buffer, rest);
+            self.ready
+                .push_back(Ok(ResponseEvent::OutputTextDelta(sentence)));
+        }
+    }
+
+    fn flush(&mut self) {
+        if !self.buffer.is_empty() {
+            let remainder = std::mem::take(&mut self.buffer);
+            self.ready
+                .push_back(Ok(ResponseEvent::OutputTextDelta(remainder)));
+        }
+    }
+}
+
+impl<S> Stream for SentenceChunks<S>
+where
+    S: Stream<Item = Result<ResponseEvent>> + Unpin,
+{
+    type Item = Result<ResponseEvent>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+
+        loop {
+            if let Some(ev) = this.ready.pop_front() {
+                return Poll::Ready(Some(ev));
+            }
+            if this.done {
+                return Poll::Ready(None);
+            }
+
+            match Pin::new(&mut this.inner).poll_next(cx) {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(None) => {
+                    this.done = true;
+                    this.flush();
+                }
+                Poll::Ready(Some(Ok(ResponseEvent::OutputTextDelta(delta)))) => {
+                    this.buffer.push_str(&delta);
+                    this.split_sentences();
+                }
+                Poll::Ready(Some(other)) => {
+                    this.flush();
+                    this.ready.push_back(other);
+                }
+            }
+        }
+    }
+}
+
+/// What a [`ResponseStream`] produced before it completed or was interrupted.
+#[derive(Debug)]
+pub struct InterruptibleOutput {
+    /// Items the model finished before the stream ended.
+    pub items: Vec<ResponseItem>,
+    /// Assistant text streamed for an item that never finished, set only when
+    /// the stream was interrupted mid-item.
+    pub partial: Option<ResponseItem>,
+}
+
+/// Consumes `stream` until it completes or `signal` resolves, whichever comes
+/// first. `signal` is typically `tokio::signal::ctrl_c()` but may be any
+/// future, which keeps this testable and lets embedders use their own
+/// interrupt source.
+///
+/// On interruption the stream is cancelled and the text received since the
+/// last finished item becomes an assistant message in
+/// [`InterruptibleOutput::partial`]. When `recorder` is provided that message
+/// is also persisted, so the rollout matches what the user saw. Events already
+/// buffered when `signal` fires are still consumed.
+pub async fn consume_interruptible<F>(
+    mut stream: ResponseStream,
+    signal: F,
+    recorder: Option<&RolloutRecorder>,
+) -> Result<InterruptibleOutput>
+where
+    F: Future,
+{
+    let mut output = InterruptibleOutput {
+        items: Vec::new(),
+        partial: None,
+    };
+    let mut text = String::new();
+    tokio::pin!(signal);
+
+    loop {
+        tokio::select! {
+            biased;
+            event = stream.next() => match event {
+                Some(Ok(ResponseEvent::OutputTextDelta(delta))) => text.push_str(&delta),
+                Some(Ok(ResponseEvent::OutputItemDone(item))) => {
+                    text.clear();
+                    output.items.push(item);
+                }
+                Some(Ok(ResponseEvent::Completed { .. })) | None => return Ok(output),
+                Some(Ok(_)) => {}
+                Some(Err(e)) => return Err(e),
+            },
+            _ = &mut signal => {
+                stream.cancel();
+                break;
+            }
+        }
+    }
+
+    if !text.is_empty() {
+        let partial = ResponseItem::Message {
+            role: "assistant".to_string(),
+            content: vec![ContentItem::OutputText { text }],
+            name: None,
+        };
+        if let Some(recorder) = recorder {
+            if let Err(e) = recorder.record_items(std::slice::from_ref(&partial)).await {
+                warn!("failed to record interrupted message: {e}");
+            }
+        }
+        output.partial = Some(partial);
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+    use super::*;
+    use crate::models::FunctionCallOutputPayload;
+    use pretty_assertions::assert_eq;
+
+    fn prompt_with_user_instructions(user_instructions: Option<&str>) -> Prompt {
+        Prompt {
+            user_instructions: user_instructions.map(str::to_string),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn full_instructions_are_cached_for_identical_inputs() {
+        let first = prompt_with_user_instructions(Some("be terse")).get_full_instructions("o3");
+        let second = prompt_with_user_instructions(Some("be terse")).get_full_instructions("o3");
+
+        assert_eq!(first, second);
+        assert!(Arc::ptr_eq(&first, &second));
+        assert!(first.ends_with("be terse"));
+    }
+
+    #[test]
+    fn instructions_cache_compares_texts_and_evicts_oldest() {
+        let key = |user| FullInstructionsKey {
+            model: "o3",
+            base_instructions: None,
+            user_instructions: Some(user),
+        };
+        let mut cache = InstructionsCache::new(2);
+        let first = cache.insert(key("a").hash_value(), &key("a"), Arc::from("A"));
+        assert_eq!(cache.get(key("a").hash_value(), &key("a")), Some(first));
+        // An entry whose hash matches but whose text does not is a miss.
+        assert_eq!(cache.get(key("a").hash_value(), &key("b")), None);
+
+        cache.insert(key("b").hash_value(), &key("b"), Arc::from("B"));
+        cache.insert(key("c").hash_value(), &key("c"), Arc::from("C"));
+        assert_eq!(cache.entries.len(), 2);
+        assert_eq!(cache.get(key("a").hash_value(), &key("a")), None);
+        assert_eq!(
+            cache.get(key("c").hash_value(), &key("c")),
+            Some(Arc::from("C"))
+        );
+    }
+
+    #[test]
+    fn user_instructions_are_normalized_when_requested() {
+        let raw = "\u{feff}Use tabs.  \r\n\r\n- keep lines short\t\r\nThanks \n";
+        let mut prompt = prompt_with_user_instructions(Some(raw));
+        assert!(prompt.get_full_instructions("o3").ends_with(raw));
+
+        prompt.normalize_user_instructions = true;
+        assert!(
+            prompt
+                .get_full_instructions("o3")
+                .ends_with("\nUse tabs.\n\n- keep lines short\nThanks\n")
+        );
+    }
+
+    #[test]
+    fn full_instructions_change_with_user_instructions() {
+        let before = prompt_with_user_instructions(Some("use tabs")).get_full_instructions("o3");
+        let after = prompt_with_user_instructions(Some("use spaces")).get_full_instructions("o3");
+        let none = prompt_with_user_instructions(None).get_full_instructions("o3");
+
+        assert!(before.ends_with("use tabs"));
+        assert!(after.ends_with("use spaces"));
+        assert_eq!(&*none, BASE_INSTRUCTIONS);
+    }
+
+    fn user_text(text: &str) -> ResponseItem {
+        ResponseItem::Message {
+            role: "user".to_string(),
+            content: vec![ContentItem::InputText {
+                text: text.to_string(),
+            }],
+            name: None,
+        }
+    }
+
+    #[test]
+    fn adjacent_user_messages_are_merged() {
+        let mut prompt = Prompt {
+            input: vec![user_text("a"), user_text("b"), user_text("c")],
+            ..Default::default()
+        };
+        prompt.normalize_input();
+
+        assert_eq!(
+            prompt.input,
+            vec![ResponseItem::Message {
+                role: "user".to_string(),
+                content: ["a", "b", "c"]
+                    .into_iter()
+                    .map(|text| ContentItem::InputText {
+                        text: text.to_string(),
+                    })
+                    .collect(),
+                name: None,
+            }]
+        );
+    }
+
+    #[test]
+    fn messages_are_not_merged_across_tool_calls() {
+        let call = ResponseItem::FunctionCall {
+            name: "shell".to_string(),
+            arguments: "{}".to_string(),
+            call_id: "call_1".to_string(),
+        };
+        let output = ResponseItem::FunctionCallOutput {
+            call_id: "call_1".to_string(),
+            output: FunctionCallOutputPayload {
+                content: "ok".to_string(),
+                success: Some(true),
+                exit_code: None,
+                duration_ms: None,
+            },
+        };
+        let input = vec![
+            user_text("a"),
+            call.clone(),
+            user_text("b"),
+            output.clone(),
+            user_text("c"),
+        ];
+        let mut prompt = Prompt {
+            input: input.clone(),
+            ..Default::default()
+        };
+        prompt.normalize_input();
+
+        assert_eq!(prompt.input, input);
+    }
+
+    #[test]
+    fn every_matching_instruction_fragment_is_appended_in_order() {
+        let rules = [
+            InstructionFragment {
+                applies_to: |model| model.starts_with("gpt-"),
+                text: "Use the tool call format exactly.",
+            },
+            InstructionFragment {
+                applies_to: |model| model.starts_with("o"),
+                text: "Reason step by step.",
+            },
+            InstructionFragment {
+                applies_to: |model| model.ends_with("-mini"),
+                text: "Do not use Markdown.",
+            },
+        ];
+
+        assert_eq!(
+            instruction_fragments(&rules, "gpt-4.1-mini").collect::<Vec<_>>(),
+            vec!["Use the tool call format exactly.", "Do not use Markdown."]
+        );
+        assert_eq!(
+            instruction_fragments(&rules, "o3").collect::<Vec<_>>(),
+            vec!["Reason step by step."]
+        );
+        assert_eq!(instruction_fragments(&rules, "codex").count(), 0);
+    }
+
+    #[tokio::test]
+    async fn base_instructions_file_replaces_built_in_instructions() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("prompt.md");
+        std::fs::write(&path, "You are a release bot.").unwrap();
+        let mut config = config_with_model("o3");
+        config.base_instructions_file = Some(path);
+
+        let prompt = Prompt {
+            base_instructions_override: load_base_instructions(&config).await.unwrap(),
+            ..prompt_with_user_instructions(Some("ship it"))
+        };
+
+        assert_eq!(
+            &*prompt.get_full_instructions("o3"),
+            "You are a release bot.\nship it"
+        );
+    }
+
+    #[tokio::test]
+    async fn missing_base_instructions_file_is_an_error() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let mut config = config_with_model("o3");
+        config.base_instructions_file = Some(dir.path().join("missing.md"));
+
+        let err = load_base_instructions(&config).await.unwrap_err();
+        assert!(err.contains("missing.md"), "{err}");
+    }
+
+    fn config_with_model(model: &str) -> Config {
+        let codex_home = tempfile::TempDir::new().unwrap();
+        let mut config = Config::load_from_base_config_with_overrides(
+            crate::config::ConfigToml::default(),
+            crate::config::ConfigOverrides::default(),
+            codex_home.path().to_path_buf(),
+        )
+        .unwrap();
+        config.model = model.to_string();
+        config
+    }
+
+    fn reasoning_json(model: &str, max_reasoning_tokens: Option<u64>) -> serde_json::Value {
+        let mut config = config_with_model(model);
+        config.model_max_reasoning_tokens = max_reasoning_tokens;
+        let reasoning = create_reasoning_param_for_request(
+            &config,
+            &config.model,
+            ReasoningEffortConfig::Medium,
+            ReasoningSummaryConfig::Auto,
+        );
+        serde_json::to_value(reasoning).unwrap()
+    }
+
+    #[test]
+    fn max_reasoning_tokens_serialized_only_when_set_and_reasoning_active() {
+        assert_eq!(
+            reasoning_json("o3", Some(2048)),
+            serde_json::json!({
+                "effort": "medium",
+                "summary": "auto",
+                "max_reasoning_tokens": 2048,
+            })
+        );
+        assert_eq!(
+            reasoning_json("o3", None),
+            serde_json::json!({"effort": "medium", "summary": "auto"})
+        );
+        assert_eq!(
+            reasoning_json("gpt-4.1", Some(2048)),
+            serde_json::Value::Null
+        );
+    }
+
+    #[test]
+    fn summary_language_instruction_is_injected_when_configured() {
+        let reasoning = Reasoning {
+            effort: OpenAiReasoningEffort::Medium,
+            summary: Some(OpenAiReasoningSummary::Auto),
+            max_reasoning_tokens: None,
+        };
+        let base: Arc<str> = Arc::from("base");
+
+        let localized =
+            with_reasoning_summary_language(base.clone(), Some(&reasoning), Some("French"));
+        assert_eq!(
+            &*localized,
+            "base\nWrite your reasoning summaries in French."
+        );
+
+        let unset = with_reasoning_summary_language(base.clone(), Some(&reasoning), None);
+        assert!(Arc::ptr_eq(&unset, &base));
+
+        let no_reasoning = with_reasoning_summary_language(base.clone(), None, Some("French"));
+        assert!(Arc::ptr_eq(&no_reasoning, &base));
+    }
+
+    #[tokio::test]
+    async fn interruption_captures_partial_message() {
+        let (tx, rx) = mpsc::channel::<Result<ResponseEvent>>(16);
+        let (interrupt_tx, interrupt_rx) = tokio::sync::oneshot::channel::<()>();
+        let stream = ResponseStream {
+            rx_event: rx,
+            cancel_token: CancellationToken::new(),
+        };
+        let cancel_token = stream.cancel_token.clone();
+
+        let finished = ResponseItem::Message {
+            role: "assistant".to_string(),
+            content: vec![ContentItem::OutputText {
+                text: "first".to_string(),
+            }],
+            name: None,
+        };
+        tx.send(Ok(ResponseEvent::OutputItemDone(finished)))
+            .await
+            .unwrap();
+        for fragment in ["Half a ", "sent"] {
+            tx.send(Ok(ResponseEvent::OutputTextDelta(fragment.to_string())))
+                .await
+                .unwrap();
+        }
+        interrupt_tx.send(()).unwrap();
+
+        let output = consume_interruptible(stream, interrupt_rx, None)
+            .await
+            .unwrap();
+
+        assert!(cancel_token.is_cancelled());
+        assert_eq!(output.items.len(), 1);
+        match output.partial {
+            Some(ResponseItem::Message { role, content, .. }) => {
+                assert_eq!(role, "assistant");
+                assert!(matches!(
+                    content.as_slice(),
+                    [ContentItem::OutputText { text }] if text == "Half a sent"
+                ));
+            }
+            other => panic!("expected partial message, got {other:?}"),
+        }
+        // `tx` is still alive, so only the interrupt could have ended the
+        // stream.
+        drop(tx);
+    }
+
+    #[tokio::test]
+    async fn sentence_chunks_split_on_boundaries_and_flush_remainder() {
+        let (tx, rx) = mpsc::channel::<Result<ResponseEvent>>(16);
+        for fragment in ["Hel", "lo wor", "ld. How", " are you?", " Fine"] {
+            tx.send(Ok(ResponseEvent::OutputTextDelta(fragment.to_string())))
+                .await
+                .unwrap();
+        }
+        tx.send(Ok(ResponseEvent::Completed {
+            response_id: "resp".to_string(),
+            token_usage: None,
+        }))
+        .await
+        .unwrap();
+        drop(tx);
+
+        let stream = ResponseStream {
+            rx_event: rx,
+            cancel_token: CancellationToken::new(),
+        };
+        let events: Vec<ResponseEvent> = stream
+            .sentence_chunks()
+            .map(|ev| ev.unwrap())
+            .collect()
+            .await;
+
+        let deltas: Vec<&str> = events
+            .iter()
+            .filter_map(|ev| match ev {
+                ResponseEvent::OutputTextDelta(delta) => Some(delta.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(deltas, vec!["Hello world.", " How are you?", " Fine"]);
+        assert_eq!(events.len(), 4);
+        assert!(matches!(events[3], ResponseEvent::Completed { .. }));
+    }
+
+    #[test]
+    fn include_options_map_to_wire_strings() {
+        let cases = [
+            (
+                IncludeOption::ReasoningEncryptedContent,
+                "reasoning.encrypted_content",
+            ),
+            (
+                IncludeOption::FileSearchCallResults,
+                "file_search_call.results",
+            ),
+            (
+                IncludeOption::MessageInputImageUrl,
+                "message.input_image.image_url",
+            ),
+            (
+                IncludeOption::ComputerCallOutputImageUrl,
+                "computer_call_output.output.image_url",
+            ),
+            (
+                IncludeOption::CodeInterpreterCallOutputs,
+                "code_interpreter_call.outputs",
+            ),
+        ];
+        for (option, expected) in cases {
+            assert_eq!(option.as_wire_str(), expected);
+        }
+    }
+
+    #[test]
+    fn include_is_serialized_only_when_requested() {
+        let input = Vec::new();
+        let mut request = ResponsesApiRequest {
+            model: "o3",
+            instructions: "",
+            input: &input,
+            tools: &[],
+            tool_choice: "auto",
+            parallel_tool_calls: false,
+            reasoning: None,
+            previous_response_id: None,
+            store: false,
+            stream: true,
+            include: Prompt::default().include_strings(),
+            stop: None,
+        };
+        let json = serde_json::to_value(&request).unwrap();
+        assert!(json.get("include").is_none());
+
+        let prompt = Prompt {
+            includes: vec![IncludeOption::ReasoningEncryptedContent],
+            ..Default::default()
+        };
+        request.include = prompt.include_strings();
+        let json = serde_json::to_value(&request).unwrap();
+        assert_eq!(
+            json["include"],
+            serde_json::json!(["reasoning.encrypted_content"])
+        );
+    }
+
+    #[test]
+    fn model_override_applies_to_request_and_reasoning_detection() {
+        let config = config_with_model("gpt-4.1");
+        let prompt = Prompt {
+            model_override: Some("o3-2025-04-16".to_string()),
+            ..Default::default()
+        };
+        let model = prompt.model(&config.model);
+        assert_eq!(Prompt::default().model(&config.model), "gpt-4.1");
+        assert!(
+            create_reasoning_param_for_request(
+                &config,
+                &config.model,
+                ReasoningEffortConfig::Medium,
+                ReasoningSummaryConfig::Auto,
+            )
+            .is_none()
+        );
+
+        let input = Vec::new();
+        let request = ResponsesApiRequest {
+            model,
+            instructions: "",
+            input: &input,
+            tools: &[],
+            tool_choice: "auto",
+            parallel_tool_calls: false,
+            reasoning: create_reasoning_param_for_request(
+                &config,
+                model,
+                ReasoningEffortConfig::Medium,
+                ReasoningSummaryConfig::Auto,
+            ),
+            previous_response_id: None,
+            store: false,
+            stream: true,
+            include: Vec::new(),
+            stop: None,
+        };
+        let json = serde_json::to_value(&request).unwrap();
+        assert_eq!(json["model"], "o3-2025-04-16");
+        assert_eq!(
+            json["reasoning"],
+            serde_json::json!({"effort": "medium", "summary": "auto"})
+        );
+    }
+
+    #[test]
+    fn stateless_reasoning_preset_builds_a_consistent_request() {
+        let config = config_with_model("o3");
+        let prompt = Prompt {
+            prev_id: Some("resp_123".to_string()),
+            ..Prompt::stateless_reasoning()
+        };
+        let reasoning = create_reasoning_param_for_request(
+            &config,
+            prompt.model(&config.model),
+            ReasoningEffortConfig::Medium,
+            ReasoningSummaryConfig::Auto,
+        );
+        let include = prompt
+            .include_strings_for_request(reasoning.as_ref(), true)
+            .unwrap();
+        let request = ResponsesApiRequest {
+            model: prompt.model(&config.model),
+            instructions: "",
+            input: &prompt.input,
+            tools: &[],
+            tool_choice: "auto",
+            parallel_tool_calls: false,
+            reasoning,
+            previous_response_id: prompt.prev_id.clone(),
+            store: prompt.store,
+            stream: true,
+            include,
+            stop: None,
+        };
+        let json = serde_json::to_value(&request).unwrap();
+        assert_eq!(json["store"], false);
+        assert_eq!(
+            json["include"],
+            serde_json::json!(["reasoning.encrypted_content"])
+        );
+        assert_eq!(
+            json["reasoning"],
+            serde_json::json!({"effort": "medium", "summary": "auto"})
+        );
+    }
+
+    #[test]
+    fn parallel_tool_calls_follows_prompt_setting() {
+        let input = Vec::new();
+        let request_json = |prompt: &Prompt| {
+            serde_json::to_value(ResponsesApiRequest {
+                model: "o3",
+                instructions: "",
+                input: &input,
+                tools: &[],
+                tool_choice: "auto",
+                parallel_tool_calls: prompt.parallel_tool_calls(),
+                reasoning: None,
+                previous_response_id: None,
+                store: false,
+                stream: true,
+                include: Vec::new(),
+                stop: None,
+            })
+            .unwrap()
+        };
+
+        for (setting, expected) in [(None, false), (Some(false), false), (Some(true), true)] {
+            let prompt = Prompt {
+                parallel_tool_calls: setting,
+                ..Default::default()
+            };
+            assert_eq!(request_json(&prompt)["parallel_tool_calls"], expected);
+        }
+    }
+
+    #[test]
+    fn stateless_reasoning_requires_encrypted_content() {
+        let reasoning = Reasoning {
+            effort: OpenAiReasoningEffort::Medium,
+            summary: None,
+            max_reasoning_tokens: None,
+        };
+        let prompt = Prompt {
+            store: false,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            prompt
+                .include_strings_for_request(Some(&reasoning), false)
+                .unwrap(),
+            vec!["reasoning.encrypted_content".to_string()]
+        );
+        assert!(matches!(
+            prompt.include_strings_for_request(Some(&reasoning), true),
+            Err(CodexErr::MissingReasoningInclude)
+        ));
+
+        // Nothing to enforce without reasoning or with server-side storage.
+        assert!(
+            prompt
+                .include_strings_for_request(None, true)
+                .unwrap()
+                .is_empty()
+        );
+        let stored = Prompt {
+            store: true,
+            ..Default::default()
+        };
+        assert!(
+            stored
+                .include_strings_for_request(Some(&reasoning), true)
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn stop_is_serialized_only_when_non_empty() {
+        let input = Vec::new();
+        let request_json = |prompt: &Prompt| {
+            serde_json::to_value(ResponsesApiRequest {
+                model: "o3",
+                instructions: "",
+                input: &input,
+                tools: &[],
+                tool_choice: "auto",
+                parallel_tool_calls: false,
+                reasoning: None,
+                previous_response_id: None,
+                store: false,
+                stream: true,
+                include: Vec::new(),
+                stop: prompt.stop_sequences(4).unwrap(),
+            })
+            .unwrap()
+        };
+
+        assert!(request_json(&Prompt::default()).get("stop").is_none());
+
+        let empty = Prompt {
+            stop: Some(Vec::new()),
+            ..Default::default()
+        };
+        assert!(request_json(&empty).get("stop").is_none());
+
+        let with_stop = Prompt {
+            stop: Some(vec!["END".to_string()]),
+            ..Default::default()
+        };
+        assert_eq!(request_json(&with_stop)["stop"], serde_json::json!(["END"]));
+    }
+
+    #[test]
+    fn stop_sequences_over_provider_max_are_refused() {
+        let prompt = Prompt {
+            stop: Some(vec!["a".into(), "b".into(), "c".into()]),
+            ..Default::default()
+        };
+        let err = prompt.stop_sequences(2).unwrap_err();
+        assert!(
+            matches!(
+                err,
+                CodexErr::TooManyStopSequences {
+                    requested: 3,
+                    max: 2
+                }
+            ),
+            "unexpected error: {err:?}"
+        );
+        assert_eq!(
+            err.to_string(),
+            "3 stop sequences requested but the provider accepts at most 2"
+        );
+        assert_eq!(prompt.stop_sequences(3).unwrap().map(|s| s.len()), Some(3));
+    }
+
+    #[test]
+    fn full_instructions_change_with_model() {
+        let prompt = prompt_with_user_instructions(Some("hello"));
+        let o3 = prompt.get_full_instructions("o3");
+        let gpt41 = prompt.get_full_instructions("gpt-4.1");
+
+        assert!(!o3.contains(APPLY_PATCH_TOOL_INSTRUCTIONS));
+        assert!(gpt41.ends_with(APPLY_PATCH_TOOL_INSTRUCTIONS));
+    }
+}
@@ -10,6 +10,7 @@ use std::sync::Arc;
 use std::sync::Mutex;
 use std::sync::atomic::AtomicU64;
 use std::time::Duration;
+use std::time::Instant;
 
 use anyhow::Context;
 use async_channel::Receiver;
@@ -27,6 +28,8 @@ use serde_json;
 use tokio::sync::Notify;
 use tokio::sync::oneshot;
 use tokio::task::AbortHandle;
+use tracing::Instrument;
+use tracing::Span;
 use tracing::debug;
 use tracing::error;
 use tracing::info;
@@ -38,9 +41,14 @@ use crate::WireApi;
 use crate::client::ModelClient;
 use crate::client_common::Prompt;
 use crate::client_common::ResponseEvent;
+use crate::client_common::load_base_instructions;
 use crate::config::Config;
 use crate::config_types::ShellEnvironmentPolicy;
 use crate::conversation_history::ConversationHistory;
+use crate::conversation_history::dedupe_images;
+use crate::conversation_tracing::TraceContext;
+use crate::conversation_tracing::create_function_call_output_span;
+use crate::conversation_tracing::record_function_output;
 use crate::error::CodexErr;
 use crate::error::Result as CodexResult;
 use crate::error::SandboxErr;
@@ -49,6 +57,8 @@ use crate::exec::ExecToolCallOutput;
 use crate::exec::SandboxType;
 use crate::exec::process_exec_tool_call;
 use crate::exec_env::create_env;
+use crate::image_upload::ImageUploader;
+use crate::image_upload::upload_local_images;
 use crate::mcp_connection_manager::McpConnectionManager;
 use crate::mcp_tool_call::handle_mcp_tool_call;
 use crate::models::ContentItem;
@@ -201,6 +211,19 @@ pub(crate) struct Session {
     rollout: Mutex<Option<RolloutRecorder>>,
     state: Mutex<State>,
     codex_linux_sandbox_exe: Option<PathBuf>,
+    /// Replace repeated identical images in the transcript with a reference.
+    dedupe_images: bool,
+    normalize_user_instructions: bool,
+    parallel_tool_calls: Option<bool>,
+    /// Replacement for runs of spaces in function call outputs, if enabled.
+    collapse_tool_output_spaces: Option<String>,
+    /// Size limit for function call outputs sent to the model, if any.
+    max_tool_output_bytes: Option<usize>,
+    /// Contents of `Config::base_instructions_file`, read when the session
+    /// was configured.
+    base_instructions: Option<String>,
+    /// Uploader for local images, see [`Config::image_uploader`].
+    image_uploader: Option<Arc<dyn ImageUploader>>,
 }
 
 impl Session {
@@ -223,9 +246,22 @@ struct State {
     pending_approvals: HashMap<String, oneshot::Sender<ReviewDecision>>,
     pending_input: Vec<ResponseInputItem>,
     zdr_transcript: Option<ConversationHistory>,
+    /// Token and turn totals written to the rollout footer when the session
+    /// ends.
+    usage_totals: crate::rollout::SessionSummaryFooter,
+    /// 1-based index of the current model turn, attached to spans.
+    turn_index: u64,
 }
 
 impl Session {
+    /// Session and current turn, for attaching to spans.
+    pub(crate) fn trace_context(&self) -> TraceContext {
+        TraceContext {
+            session_id: self.client.session_id(),
+            turn_index: self.state.lock().unwrap().turn_index,
+        }
+    }
+
     pub fn set_task(&self, task: AgentTask) {
         let mut state = self.state.lock().unwrap();
         if let Some(current_task) = state.current_task.take() {
@@ -345,6 +381,18 @@ impl Session {
         }
     }
 
+    async fn record_turn_timing(&self, timing: crate::rollout::TurnTiming) {
+        let recorder = {
+            let guard = self.rollout.lock().unwrap();
+            guard.as_ref().cloned()
+        };
+        if let Some(rec) = recorder {
+            if let Err(e) = rec.record_turn_timing(timing).await {
+                error!("failed to record rollout turn timing: {e:#}");
+            }
+        }
+    }
+
     async fn notify_exec_command_begin(&self, sub_id: &str, call_id: &str, params: &ExecParams) {
         let event = Event {
             id: sub_id.to_string(),
@@ -600,12 +648,26 @@ async fn submission_loop(
                     }
                     return;
                 }
+                let base_instructions = match load_base_instructions(&config).await {
+                    Ok(base_instructions) => base_instructions,
+                    Err(message) => {
+                        error!(message);
+                        let event = Event {
+                            id: sub.id,
+                            msg: EventMsg::Error(ErrorEvent { message }),
+                        };
+                        if let Err(e) = tx_event.send(event).await {
+                            error!("failed to send error message: {e:?}");
+                        }
+                        return;
+                    }
+                };
                 // Optionally resume an existing rollout.
                 let mut restored_items: Option<Vec<ResponseItem>> = None;
                 let mut restored_prev_id: Option<String> = None;
                 let rollout_recorder: Option<RolloutRecorder> =
                     if let Some(path) = resume_path.as_ref() {
-                        match RolloutRecorder::resume(path).await {
+                        match RolloutRecorder::resume(&config, path).await {
                             Ok((rec, saved)) => {
                                 session_id = saved.session_id;
                                 restored_prev_id = saved.state.previous_response_id;
@@ -625,15 +687,17 @@ async fn submission_loop(
 
                 let rollout_recorder = match rollout_recorder {
                     Some(rec) => Some(rec),
-                    None => match RolloutRecorder::new(&config, session_id, instructions.clone())
-                        .await
-                    {
-                        Ok(r) => Some(r),
-                        Err(e) => {
-                            warn!("failed to initialise rollout recorder: {e}");
-                            None
+                    None => {
+                        match RolloutRecorder::new(&config, session_id, instructions.clone(), None)
+                            .await
+                        {
+                            Ok(r) => Some(r),
+                            Err(e) => {
+                                warn!("failed to initialise rollout recorder: {e}");
+                                None
+                            }
                         }
-                    },
+                    }
                 };
 
                 let client = ModelClient::new(
@@ -710,6 +774,13 @@ async fn submission_loop(
                     state: Mutex::new(state),
                     rollout: Mutex::new(rollout_recorder),
                     codex_linux_sandbox_exe: config.codex_linux_sandbox_exe.clone(),
+                    dedupe_images: config.dedupe_images,
+                    normalize_user_instructions: config.normalize_user_instructions,
+                    parallel_tool_calls: config.parallel_tool_calls,
+                    collapse_tool_output_spaces: config.collapse_tool_output_spaces.clone(),
+                    max_tool_output_bytes: config.max_tool_output_bytes,
+                    base_instructions,
+                    image_uploader: config.image_uploader.clone(),
                 }));
 
                 // Patch restored state into the newly created session.
@@ -754,6 +825,7 @@ async fn submission_loop(
                         continue;
                     }
                 };
+                let items = upload_local_images(items, sess.image_uploader.as_deref()).await;
 
                 // attempt to inject input into current task
                 if let Err(items) = sess.inject_input(items) {
@@ -836,6 +908,21 @@ async fn submission_loop(
             }
         }
     }
+
+    // Make sure the final turn reaches the rollout before the process exits.
+    let recorder = sess.and_then(|sess| {
+        let summary = sess.state.lock().unwrap().usage_totals.clone();
+        let recorder = sess.rollout.lock().unwrap().take();
+        recorder.map(|recorder| (recorder, summary))
+    });
+    if let Some((recorder, summary)) = recorder {
+        if let Err(e) = recorder.record_summary(summary).await {
+            warn!("failed to record rollout summary: {e}");
+        }
+        if let Err(e) = recorder.shutdown().await {
+            warn!("failed to flush rollout: {e}");
+        }
+    }
     debug!("Agent loop exited");
 }
 
@@ -873,7 +960,7 @@ async fn run_task(sess: Arc<Session>, sub_id: String, input: Vec<InputItem>) {
     loop {
         let mut net_new_turn_input = input_for_next_turn
             .drain(..)
-            .map(ResponseItem::from)
+            .flat_map(ResponseInputItem::into_response_items)
             .collect::<Vec<_>>();
 
         // Note that pending_input would be something like a message the user
@@ -904,7 +991,12 @@ async fn run_task(sess: Arc<Session>, sub_id: String, input: Vec<InputItem>) {
                 // `pending_input` that was injected while the model was
                 // running. We need to add that to the conversation history
                 // so that the model can see it in the next turn.
-                [transcript.contents(), pending_input].concat()
+                let input = [transcript.contents(), pending_input].concat();
+                if sess.dedupe_images {
+                    dedupe_images(input)
+                } else {
+                    input
+                }
             } else {
                 // In practice, net_new_turn_input should contain only:
                 // - User messages
@@ -969,25 +1061,12 @@ async fn run_task(sess: Arc<Session>, sub_id: String, input: Vec<InputItem>) {
                             Some(ResponseInputItem::McpToolCallOutput { call_id, result }),
                         ) => {
                             items_to_record_in_conversation_history.push(item);
-                            let (content, success): (String, Option<bool>) = match result {
-                                Ok(CallToolResult {
-                                    content,
-                                    is_error,
-                                    structured_content: _,
-                                }) => match serde_json::to_string(content) {
-                                    Ok(content) => (content, *is_error),
-                                    Err(e) => {
-                                        warn!("Failed to serialize MCP tool call output: {e}");
-                                        (e.to_string(), Some(true))
-                                    }
-                                },
-                                Err(e) => (e.clone(), Some(true)),
-                            };
-                            items_to_record_in_conversation_history.push(
-                                ResponseItem::FunctionCallOutput {
+                            items_to_record_in_conversation_history.extend(
+                                ResponseInputItem::McpToolCallOutput {
                                     call_id: call_id.clone(),
-                                    output: FunctionCallOutputPayload { content, success },
-                                },
+                                    result: result.clone(),
+                                }
+                                .into_response_items(),
                             );
                         }
                         (ResponseItem::Reasoning { .. }, None) => {
@@ -1051,7 +1130,11 @@ async fn run_turn(
 ) -> CodexResult<Vec<ProcessedResponseItem>> {
     // Decide whether to use server-side storage (previous_response_id) or disable it
     let (prev_id, store) = {
-        let state = sess.state.lock().unwrap();
+        let mut state = sess.state.lock().unwrap();
+        state.turn_index += 1;
+        if let Some(rollout) = sess.rollout.lock().unwrap().as_ref() {
+            rollout.set_turn_index(state.turn_index);
+        }
         let store = state.zdr_transcript.is_none();
         let prev_id = if store {
             state.previous_response_id.clone()
@@ -1069,7 +1152,15 @@ async fn run_turn(
         prev_id,
         user_instructions: sess.instructions.clone(),
         store,
+        tools: Vec::new(),
         extra_tools,
+        includes: Vec::new(),
+        stop: None,
+        normalize_user_instructions: sess.normalize_user_instructions,
+        parallel_tool_calls: sess.parallel_tool_calls,
+        model_override: None,
+        base_instructions_override: sess.base_instructions.clone(),
+        trace: sess.trace_context(),
     };
 
     let mut retries = 0;
@@ -1078,6 +1169,10 @@ async fn run_turn(
             Ok(output) => return Ok(output),
             Err(CodexErr::Interrupted) => return Err(CodexErr::Interrupted),
             Err(CodexErr::EnvVar(var)) => return Err(CodexErr::EnvVar(var)),
+            Err(CodexErr::MissingReasoningInclude) => {
+                return Err(CodexErr::MissingReasoningInclude);
+            }
+            Err(e @ CodexErr::TooManyStopSequences { .. }) => return Err(e),
             Err(e) => {
                 // Use the configured provider-specific stream retry budget.
                 let max_retries = sess.client.get_provider().stream_max_retries();
@@ -1158,6 +1253,8 @@ async fn try_run_turn(
                 output: FunctionCallOutputPayload {
                     content: "aborted".to_string(),
                     success: Some(false),
+                    exit_code: None,
+                    duration_ms: None,
                 },
             })
             .collect::<Vec<_>>()
@@ -1173,6 +1270,8 @@ async fn try_run_turn(
         })
     };
 
+    let started = Instant::now();
+    let mut first_token: Option<Duration> = None;
     let mut stream = sess.client.clone().stream(&prompt).await?;
 
     let mut output = Vec::new();
@@ -1198,6 +1297,17 @@ async fn try_run_turn(
             }
         };
 
+        if first_token.is_none()
+            && matches!(
+                event,
+                ResponseEvent::OutputTextDelta(_)
+                    | ResponseEvent::ReasoningSummaryDelta { .. }
+                    | ResponseEvent::OutputItemDone(_)
+            )
+        {
+            first_token = Some(started.elapsed());
+        }
+
         match event {
             ResponseEvent::Created => {
                 let mut state = sess.state.lock().unwrap();
@@ -1226,7 +1336,29 @@ async fn try_run_turn(
                 response_id,
                 token_usage,
             } => {
+                sess.record_turn_timing(crate::rollout::TurnTiming {
+                    time_to_first_token_ms: first_token.map(|d| d.as_millis() as u64),
+                    total_ms: started.elapsed().as_millis() as u64,
+                    input_items: prompt.input.len(),
+                    input_tokens: token_usage.as_ref().map(|u| u.input_tokens),
+                })
+                .await;
+                {
+                    let mut state = sess.state.lock().unwrap();
+                    let totals = &mut state.usage_totals;
+                    totals.turns += 1;
+                    if let Some(usage) = &token_usage {
+                        totals.input_tokens += usage.input_tokens;
+                        totals.output_tokens += usage.output_tokens;
+                        totals.total_tokens += usage.total_tokens;
+                    }
+                }
                 if let Some(token_usage) = token_usage {
+                    crate::telemetry::record_token_usage(&token_usage);
+                    crate::telemetry::record_token_histograms(
+                        prompt.model(sess.client.model()),
+                        &token_usage,
+                    );
                     sess.tx_event
                         .send(Event {
                             id: sub_id.to_string(),
@@ -1247,13 +1379,30 @@ async fn try_run_turn(
                 };
                 sess.tx_event.send(event).await.ok();
             }
-            ResponseEvent::ReasoningSummaryDelta(delta) => {
+            ResponseEvent::ReasoningSummaryDelta { item_id, delta } => {
                 let event = Event {
                     id: sub_id.to_string(),
-                    msg: EventMsg::AgentReasoningDelta(AgentReasoningDeltaEvent { delta }),
+                    msg: EventMsg::AgentReasoningDelta(AgentReasoningDeltaEvent { item_id, delta }),
                 };
                 sess.tx_event.send(event).await.ok();
             }
+            ResponseEvent::ResponseHeaders(headers) => {
+                debug!(?headers, "provider response headers");
+            }
+            ResponseEvent::ReasoningEscalated { from, to } => {
+                sess.notify_background_event(
+                    sub_id,
+                    format!("response was empty; retrying with reasoning effort `{to}` instead of `{from}`"),
+                )
+                .await;
+            }
+            ResponseEvent::ProviderFailover { from, to } => {
+                sess.notify_background_event(
+                    sub_id,
+                    format!("provider `{from}` failed; retrying with `{to}`"),
+                )
+                .await;
+            }
         }
     }
 }
@@ -1296,7 +1445,9 @@ async fn handle_response_item(
             call_id,
         } => {
             info!("FunctionCall: {arguments}");
-            Some(handle_function_call(sess, sub_id.to_string(), name, arguments, call_id).await)
+            let span = create_function_call_output_span(&sess.trace_context(), &call_id);
+            let call = handle_function_call(sess, sub_id.to_string(), name, arguments, call_id);
+            Some(traced_function_call(span, call).await)
         }
         ResponseItem::LocalShellCall {
             id,
@@ -1306,9 +1457,11 @@ async fn handle_response_item(
         } => {
             let LocalShellAction::Exec(action) = action;
             tracing::info!("LocalShellCall: {action:?}");
+            crate::telemetry::record_tool_call("local_shell");
             let params = ShellToolCallParams {
                 command: action.command,
                 workdir: action.working_directory,
+                env: action.env,
                 timeout_ms: action.timeout_ms,
             };
             let effective_call_id = match (call_id, id) {
@@ -1321,21 +1474,22 @@ async fn handle_response_item(
                         output: FunctionCallOutputPayload {
                             content: "LocalShellCall without call_id or id".to_string(),
                             success: None,
+                            exit_code: None,
+                            duration_ms: None,
                         },
                     }));
                 }
             };
 
             let exec_params = to_exec_params(params, sess);
-            Some(
-                handle_container_exec_with_params(
-                    exec_params,
-                    sess,
-                    sub_id.to_string(),
-                    effective_call_id,
-                )
-                .await,
-            )
+            let span = create_function_call_output_span(&sess.trace_context(), &effective_call_id);
+            let call = handle_container_exec_with_params(
+                exec_params,
+                sess,
+                sub_id.to_string(),
+                effective_call_id,
+            );
+            Some(traced_function_call(span, call).await)
         }
         ResponseItem::FunctionCallOutput { .. } => {
             debug!("unexpected FunctionCallOutput from stream");
@@ -1346,6 +1500,20 @@ async fn handle_response_item(
     Ok(output)
 }
 
+/// Runs a tool call inside its `function_call_output` span, so the span
+/// covers the call and parents the work it does, then records the output on
+/// it.
+async fn traced_function_call(
+    span: Span,
+    call: impl Future<Output = ResponseInputItem>,
+) -> ResponseInputItem {
+    let output = call.instrument(span.clone()).await;
+    if let ResponseInputItem::FunctionCallOutput { output, .. } = &output {
+        span.in_scope(|| record_function_output(output.success.unwrap_or(false), &output.content));
+    }
+    output
+}
+
 async fn handle_function_call(
     sess: &Session,
     sub_id: String,
@@ -1353,6 +1521,7 @@ async fn handle_function_call(
     arguments: String,
     call_id: String,
 ) -> ResponseInputItem {
+    crate::telemetry::record_tool_call(&name);
     match name.as_str() {
         "container.exec" | "shell" => {
             let params = match parse_container_exec_arguments(arguments, sess, &call_id) {
@@ -1380,6 +1549,8 @@ async fn handle_function_call(
                         output: FunctionCallOutputPayload {
                             content: format!("unsupported call: {name}"),
                             success: None,
+                            exit_code: None,
+                            duration_ms: None,
                         },
                     }
                 }
@@ -1389,11 +1560,13 @@ async fn handle_function_call(
 }
 
 fn to_exec_params(params: ShellToolCallParams, sess: &Session) -> ExecParams {
+    let mut env = create_env(&sess.shell_environment_policy);
+    env.extend(params.env.unwrap_or_default());
     ExecParams {
         command: params.command,
         cwd: sess.resolve_path(params.workdir.clone()),
         timeout_ms: params.timeout_ms,
-        env: create_env(&sess.shell_environment_policy),
+        env,
     }
 }
 
@@ -1403,20 +1576,24 @@ fn parse_container_exec_arguments(
     call_id: &str,
 ) -> Result<ExecParams, Box<ResponseInputItem>> {
     // parse command
-    match serde_json::from_str::<ShellToolCallParams>(&arguments) {
-        Ok(shell_tool_call_params) => Ok(to_exec_params(shell_tool_call_params, sess)),
-        Err(e) => {
-            // allow model to re-sample
-            let output = ResponseInputItem::FunctionCallOutput {
-                call_id: call_id.to_string(),
-                output: FunctionCallOutputPayload {
-                    content: format!("failed to parse function arguments: {e}"),
-                    success: None,
-                },
-            };
-            Err(Box::new(output))
-        }
-    }
+    let error = match serde_json::from_str::<ShellToolCallParams>(&arguments) {
+        Ok(shell_tool_call_params) => match shell_tool_call_params.validate() {
+            Ok(()) => return Ok(to_exec_params(shell_tool_call_params, sess)),
+            Err(e) => format!("invalid shell command: {e}"),
+        },
+        Err(e) => format!("failed to parse function arguments: {e}"),
+    };
+    // allow model to re-sample
+    let output = ResponseInputItem::FunctionCallOutput {
+        call_id: call_id.to_string(),
+        output: FunctionCallOutputPayload {
+            content: error,
+            success: None,
+            exit_code: None,
+            duration_ms: None,
+        },
+    };
+    Err(Box::new(output))
 }
 
 async fn handle_container_exec_with_params(
@@ -1439,6 +1616,8 @@ async fn handle_container_exec_with_params(
                 output: FunctionCallOutputPayload {
                     content: format!("error: {parse_error:#}"),
                     success: None,
+                    exit_code: None,
+                    duration_ms: None,
                 },
             };
         }
@@ -1480,6 +1659,8 @@ async fn handle_container_exec_with_params(
                         output: FunctionCallOutputPayload {
                             content: "exec command rejected by user".to_string(),
                             success: None,
+                            exit_code: None,
+                            duration_ms: None,
                         },
                     };
                 }
@@ -1496,6 +1677,8 @@ async fn handle_container_exec_with_params(
                 output: FunctionCallOutputPayload {
                     content: format!("exec command rejected: {reason}"),
                     success: None,
+                    exit_code: None,
+                    duration_ms: None,
                 },
             };
         }
@@ -1515,29 +1698,22 @@ async fn handle_container_exec_with_params(
 
     match output_result {
         Ok(output) => {
-            let ExecToolCallOutput {
-                exit_code,
-                stdout,
-                stderr,
-                duration,
-            } = output;
-
-            sess.notify_exec_command_end(&sub_id, &call_id, &stdout, &stderr, exit_code)
-                .await;
-
-            let is_success = exit_code == 0;
-            let content = format_exec_output(
-                if is_success { &stdout } else { &stderr },
-                exit_code,
-                duration,
-            );
+            sess.notify_exec_command_end(
+                &sub_id,
+                &call_id,
+                &output.stdout,
+                &output.stderr,
+                output.exit_code,
+            )
+            .await;
 
             ResponseInputItem::FunctionCallOutput {
                 call_id,
-                output: FunctionCallOutputPayload {
-                    content,
-                    success: Some(is_success),
-                },
+                output: exec_output_payload(
+                    &output,
+                    sess.collapse_tool_output_spaces.as_deref(),
+                    sess.max_tool_output_bytes,
+                ),
             }
         }
         Err(CodexErr::Sandbox(error)) => {
@@ -1550,6 +1726,8 @@ async fn handle_container_exec_with_params(
                 output: FunctionCallOutputPayload {
                     content: format!("execution error: {e}"),
                     success: None,
+                    exit_code: None,
+                    duration_ms: None,
                 },
             }
         }
@@ -1573,6 +1751,8 @@ async fn handle_sandbox_error(
                     "failed in sandbox {sandbox_type:?} with execution error: {error}"
                 ),
                 success: Some(false),
+                exit_code: None,
+                duration_ms: None,
             },
         };
     }
@@ -1626,35 +1806,22 @@ async fn handle_sandbox_error(
 
             match retry_output_result {
                 Ok(retry_output) => {
-                    let ExecToolCallOutput {
-                        exit_code,
-                        stdout,
-                        stderr,
-                        duration,
-                    } = retry_output;
-
                     sess.notify_exec_command_end(
                         &sub_id,
                         &retry_call_id,
-                        &stdout,
-                        &stderr,
-                        exit_code,
+                        &retry_output.stdout,
+                        &retry_output.stderr,
+                        retry_output.exit_code,
                     )
                     .await;
 
-                    let is_success = exit_code == 0;
-                    let content = format_exec_output(
-                        if is_success { &stdout } else { &stderr },
-                        exit_code,
-                        duration,
-                    );
-
                     ResponseInputItem::FunctionCallOutput {
                         call_id,
-                        output: FunctionCallOutputPayload {
-                            content,
-                            success: Some(is_success),
-                        },
+                        output: exec_output_payload(
+                            &retry_output,
+                            sess.collapse_tool_output_spaces.as_deref(),
+                            sess.max_tool_output_bytes,
+                        ),
                     }
                 }
                 Err(e) => {
@@ -1664,6 +1831,8 @@ async fn handle_sandbox_error(
                         output: FunctionCallOutputPayload {
                             content: format!("retry failed: {e}"),
                             success: None,
+                            exit_code: None,
+                            duration_ms: None,
                         },
                     }
                 }
@@ -1676,6 +1845,8 @@ async fn handle_sandbox_error(
                 output: FunctionCallOutputPayload {
                     content: "exec command rejected by user".to_string(),
                     success: None,
+                    exit_code: None,
+                    duration_ms: None,
                 },
             }
         }
@@ -1714,6 +1885,8 @@ async fn apply_patch(
                         output: FunctionCallOutputPayload {
                             content: "patch rejected by user".to_string(),
                             success: Some(false),
+                            exit_code: None,
+                            duration_ms: None,
                         },
                     };
                 }
@@ -1725,6 +1898,8 @@ async fn apply_patch(
                 output: FunctionCallOutputPayload {
                     content: format!("patch rejected: {reason}"),
                     success: Some(false),
+                    exit_code: None,
+                    duration_ms: None,
                 },
             };
         }
@@ -1754,6 +1929,8 @@ async fn apply_patch(
                 output: FunctionCallOutputPayload {
                     content: "patch rejected by user".to_string(),
                     success: Some(false),
+                    exit_code: None,
+                    duration_ms: None,
                 },
             };
         }
@@ -1869,7 +2046,9 @@ async fn apply_patch(
             call_id,
             output: FunctionCallOutputPayload {
                 content: String::from_utf8_lossy(&stdout).to_string(),
-                success: None,
+                success: Some(true),
+                exit_code: None,
+                duration_ms: None,
             },
         },
         Err(e) => ResponseInputItem::FunctionCallOutput {
@@ -1877,6 +2056,8 @@ async fn apply_patch(
             output: FunctionCallOutputPayload {
                 content: format!("error: {e:#}, stderr: {}", String::from_utf8_lossy(&stderr)),
                 success: Some(false),
+                exit_code: None,
+                duration_ms: None,
             },
         },
     }
@@ -2053,6 +2234,44 @@ fn get_writable_roots(cwd: &Path) -> Vec<PathBuf> {
     writable_roots
 }
 
+/// Reports a finished command to the model: its stdout, or its stderr if it
+/// failed, wrapped by [`format_exec_output`]. Runs of spaces are collapsed
+/// to `collapse_spaces`, and the output is cut down to `max_bytes`, if set.
+/// Both apply to the raw output: there line breaks are still real ones rather
+/// than `\n` escapes, and cutting it keeps the JSON and the exit code intact.
+fn exec_output_payload(
+    output: &ExecToolCallOutput,
+    collapse_spaces: Option<&str>,
+    max_bytes: Option<usize>,
+) -> FunctionCallOutputPayload {
+    let is_success = output.exit_code == 0;
+    let raw = if is_success {
+        &output.stdout
+    } else {
+        &output.stderr
+    };
+    let raw = match collapse_spaces {
+        Some(replacement) => Cow::Owned(FunctionCallOutputPayload::collapse_space_runs(
+            raw,
+            replacement,
+        )),
+        None => Cow::Borrowed(raw.as_str()),
+    };
+    let raw = match max_bytes {
+        Some(limit) => Cow::Owned(FunctionCallOutputPayload::truncated(
+            raw.into_owned(),
+            limit,
+        )),
+        None => raw,
+    };
+    FunctionCallOutputPayload {
+        content: format_exec_output(&raw, output.exit_code, output.duration),
+        success: Some(is_success),
+        exit_code: Some(output.exit_code),
+        duration_ms: Some(duration_ms(output.duration)),
+    }
+}
+
 /// Exec output is a pre-serialized JSON payload
 fn format_exec_output(output: &str, exit_code: i32, duration: Duration) -> String {
     #[derive(Serialize)]
@@ -2082,9 +2301,14 @@ fn format_exec_output(output: &str, exit_code: i32, duration: Duration) -> Strin
     serde_json::to_string(&payload).expect("serialize ExecOutput")
 }
 
+/// `duration` in whole milliseconds, for [`FunctionCallOutputPayload::duration_ms`].
+fn duration_ms(duration: Duration) -> u64 {
+    u64::try_from(duration.as_millis()).unwrap_or(u64::MAX)
+}
+
 fn get_last_assistant_message_from_turn(responses: &[ResponseItem]) -> Option<String> {
     responses.iter().rev().find_map(|item| {
-        if let ResponseItem::Message { role, content } = item {
+        if let ResponseItem::Message { role, content, .. } = item {
             if role == "assistant" {
                 content.iter().rev().find_map(|ci| {
                     if let ContentItem::OutputText { text } = ci {
@@ -2113,3 +2337,83 @@ fn record_conversation_history(disable_response_storage: bool, wire_api: WireApi
         WireApi::Chat => true,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn exec_output(exit_code: i32, stdout: &str, stderr: &str) -> ExecToolCallOutput {
+        ExecToolCallOutput {
+            exit_code,
+            stdout: stdout.to_string(),
+            stderr: stderr.to_string(),
+            duration: std::time::Duration::from_millis(5),
+        }
+    }
+
+    #[tokio::test]
+    async fn tool_call_runs_inside_its_output_span() {
+        let _guard = tracing::subscriber::set_default(tracing_subscriber::registry());
+        let span = create_function_call_output_span(
+            &crate::conversation_tracing::TraceContext::default(),
+            "call_1",
+        );
+        let output = traced_function_call(span, async {
+            assert_eq!(
+                Span::current().metadata().map(|metadata| metadata.name()),
+                Some("function_call_output")
+            );
+            ResponseInputItem::FunctionCallOutput {
+                call_id: "call_1".to_string(),
+                output: FunctionCallOutputPayload {
+                    content: "done".to_string(),
+                    success: Some(true),
+                    exit_code: None,
+                    duration_ms: None,
+                },
+            }
+        })
+        .await;
+        assert!(matches!(
+            output,
+            ResponseInputItem::FunctionCallOutput { .. }
+        ));
+    }
+
+    fn model_output(payload: &FunctionCallOutputPayload) -> serde_json::Value {
+        serde_json::from_str(&payload.content).unwrap()
+    }
+
+    #[test]
+    fn exec_output_space_runs_are_collapsed_per_line() {
+        let output = exec_output(0, "NAME        READY\n    web-1       1/1\n", "");
+
+        let payload = exec_output_payload(&output, Some(" "), None);
+        let json = model_output(&payload);
+        assert_eq!(json["output"], "NAME READY\n    web-1 1/1\n");
+        assert_eq!(json["metadata"]["exit_code"], 0);
+        assert_eq!(payload.success, Some(true));
+
+        let payload = exec_output_payload(&output, None, None);
+        assert_eq!(
+            model_output(&payload)["output"],
+            "NAME        READY\n    web-1       1/1\n"
+        );
+    }
+
+    #[test]
+    fn long_exec_output_is_truncated_before_serialization() {
+        let output = exec_output(3, "", &"\"quoted\"\n".repeat(100));
+
+        let payload = exec_output_payload(&output, None, Some(20));
+        let json = model_output(&payload);
+        assert_eq!(
+            json["output"],
+            "\"quoted\"\n\"… [880 bytes truncated] …\n\"quoted\"\n"
+        );
+        assert_eq!(json["metadata"]["exit_code"], 3);
+        assert_eq!(payload.exit_code, Some(3));
+    }
+}
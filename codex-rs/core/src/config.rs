@@ -2,6 +2,7 @@ use crate::config_profile::ConfigProfile;
 use crate::config_types::History;
 use crate::config_types::McpServerConfig;
 use crate::config_types::ReasoningEffort;
+use crate::config_types::ReasoningEffortSetting;
 use crate::config_types::ReasoningSummary;
 use crate::config_types::SandboxMode;
 use crate::config_types::SandboxWorkplaceWrite;
@@ -10,6 +11,7 @@ use crate::config_types::ShellEnvironmentPolicyToml;
 use crate::config_types::Tui;
 use crate::config_types::UriBasedFileOpener;
 use crate::flags::OPENAI_DEFAULT_MODEL;
+use crate::image_upload::ImageUploader;
 use crate::model_provider_info::ModelProviderInfo;
 use crate::model_provider_info::built_in_model_providers;
 use crate::openai_model_info::get_model_info;
@@ -20,6 +22,7 @@ use serde::Deserialize;
 use std::collections::HashMap;
 use std::path::Path;
 use std::path::PathBuf;
+use std::sync::Arc;
 use toml::Value as TomlValue;
 
 /// Maximum number of bytes of the documentation that will be embedded. Larger
@@ -140,6 +143,90 @@ pub struct Config {
 
     /// Experimental rollout resume path (absolute path to .jsonl; undocumented).
     pub experimental_resume: Option<PathBuf>,
+
+    /// When `true`, a SHA-256 digest of each rollout file is written to a
+    /// `.sha256` sidecar when the session's recorder shuts down so that the
+    /// transcript can later be checked with `verify_rollout_integrity`.
+    pub rollout_integrity_hash: bool,
+
+    /// Upper bound on the tokens a reasoning model may spend on reasoning, sent
+    /// alongside the reasoning effort. `None` leaves it to the provider.
+    pub model_max_reasoning_tokens: Option<u64>,
+
+    /// Provider ids to try, in order, when the configured provider is
+    /// unreachable or keeps returning server errors.
+    pub provider_fallback: Vec<String>,
+
+    /// When `true`, reasoning items are written to the rollout alongside the
+    /// conversation so model behavior can be inspected after the fact.
+    pub rollout_persist_reasoning: bool,
+
+    /// When `true`, new rollouts are written gzip-compressed as `.jsonl.gz`.
+    pub rollout_gzip: bool,
+
+    /// When `true`, a function call output identical to one recorded shortly
+    /// before is written to the rollout as a reference to it.
+    pub rollout_dedupe_outputs: bool,
+
+    /// Language reasoning summaries should be written in, e.g. `"French"` or
+    /// `"ja-JP"`. Best-effort: it is passed to the model as an instruction.
+    pub model_reasoning_summary_language: Option<String>,
+
+    /// Directory rollouts are written to. Defaults to `$CODEX_HOME/sessions`.
+    pub rollout_dir: Option<PathBuf>,
+
+    /// When `true`, an image identical to one attached earlier in the
+    /// conversation is replaced by a short reference when the full transcript is
+    /// sent to the model.
+    pub dedupe_images: bool,
+
+    /// When `true`, a turn that completes without any visible output is re-issued
+    /// once at the next-higher reasoning effort.
+    pub escalate_reasoning_on_empty: bool,
+
+    /// Response headers (matched case-insensitively) that are captured from the
+    /// provider and surfaced as `ResponseEvent::ResponseHeaders`.
+    pub response_header_allowlist: Vec<String>,
+
+    /// When `true`, a request that asks for reasoning with response storage
+    /// disabled fails if `reasoning.encrypted_content` is not included, instead
+    /// of the include being added automatically.
+    pub strict_reasoning_include: bool,
+
+    /// When `true`, rollout filenames and session timestamps use UTC instead
+    /// of local time.
+    pub rollout_utc_timestamps: bool,
+
+    /// When set, runs of three or more spaces in shell command output are
+    /// replaced with this string before it reaches the model. Leading
+    /// indentation and line breaks are kept.
+    pub collapse_tool_output_spaces: Option<String>,
+
+    /// When set, a rollout that grows past this many bytes is continued in a
+    /// new `-partNN` file.
+    pub rollout_max_bytes: Option<u64>,
+
+    /// When `true`, user instructions are sent with a leading BOM removed,
+    /// CRLF line endings converted to LF, and trailing whitespace trimmed from
+    /// each line.
+    pub normalize_user_instructions: bool,
+
+    /// Value for the `parallel_tool_calls` field of Responses API requests.
+    /// When unset, parallel tool calls are not requested.
+    pub parallel_tool_calls: Option<bool>,
+
+    /// When set, shell command output longer than this many bytes is cut
+    /// down to its beginning and end before it reaches the model.
+    pub max_tool_output_bytes: Option<usize>,
+
+    /// File whose contents replace the built-in base instructions. It is read
+    /// each time a session is configured, so edits apply to the next session.
+    pub base_instructions_file: Option<PathBuf>,
+
+    /// Uploader for local images. When set, images attached to user input
+    /// are uploaded and referenced by URL instead of being inlined. Never
+    /// read from `config.toml`; embedders set it before spawning a session.
+    pub image_uploader: Option<Arc<dyn ImageUploader>>,
 }
 
 impl Config {
@@ -316,9 +403,13 @@ pub struct ConfigToml {
     /// UI/output. Defaults to `false`.
     pub hide_agent_reasoning: Option<bool>,
 
-    pub model_reasoning_effort: Option<ReasoningEffort>,
+    pub model_reasoning_effort: Option<ReasoningEffortSetting>,
     pub model_reasoning_summary: Option<ReasoningSummary>,
 
+    /// When `true`, an unrecognized `model_reasoning_effort` is a config
+    /// error. Otherwise it is logged and the default effort is used.
+    pub model_reasoning_effort_strict: Option<bool>,
+
     /// Override to force-enable reasoning summaries for the configured model.
     pub model_supports_reasoning_summaries: Option<bool>,
 
@@ -327,6 +418,69 @@ pub struct ConfigToml {
 
     /// Experimental rollout resume path (absolute path to .jsonl; undocumented).
     pub experimental_resume: Option<PathBuf>,
+
+    /// Write a SHA-256 sidecar next to each rollout file. Defaults to `false`.
+    pub rollout_integrity_hash: Option<bool>,
+
+    /// Maximum number of reasoning tokens per request. Must be positive.
+    pub model_max_reasoning_tokens: Option<u64>,
+
+    /// Ordered list of `model_providers` keys to fail over to.
+    pub provider_fallback: Option<Vec<String>>,
+
+    /// Keep reasoning items in rollout files. Defaults to `false`.
+    pub rollout_persist_reasoning: Option<bool>,
+
+    /// Write rollouts gzip-compressed. Defaults to `false`.
+    pub rollout_gzip: Option<bool>,
+
+    /// Record repeated function call outputs as references. Defaults to `false`.
+    pub rollout_dedupe_outputs: Option<bool>,
+
+    /// Language to request reasoning summaries in.
+    pub model_reasoning_summary_language: Option<String>,
+
+    /// Directory to write rollouts to instead of `$CODEX_HOME/sessions`.
+    pub rollout_dir: Option<PathBuf>,
+
+    /// Replace repeated identical images with a reference. Defaults to `false`.
+    pub dedupe_images: Option<bool>,
+
+    /// Retry an empty turn once at a higher reasoning effort. Defaults to `false`.
+    pub escalate_reasoning_on_empty: Option<bool>,
+
+    /// Response headers to capture. Defaults to the rate-limit headers.
+    pub response_header_allowlist: Option<Vec<String>>,
+
+    /// Fail instead of adding a missing `reasoning.encrypted_content` include.
+    /// Defaults to `false`.
+    pub strict_reasoning_include: Option<bool>,
+
+    /// Use UTC for rollout filenames and timestamps. Defaults to `false`.
+    pub rollout_utc_timestamps: Option<bool>,
+
+    /// Collapse runs of spaces in tool output. Defaults to `false`.
+    pub collapse_tool_output_spaces: Option<bool>,
+
+    /// What runs of spaces are collapsed to. Defaults to a single space.
+    pub collapse_tool_output_spaces_marker: Option<String>,
+
+    /// Rotate rollouts into a new part past this size. Unset by default.
+    pub rollout_max_bytes: Option<u64>,
+
+    /// Normalize line endings and trailing whitespace in user instructions.
+    /// Defaults to `false`.
+    pub normalize_user_instructions: Option<bool>,
+
+    /// Allow the model to make several tool calls in one response. Unset by
+    /// default, which does not request parallel calls.
+    pub parallel_tool_calls: Option<bool>,
+
+    /// Truncate function call outputs past this size. Unset by default.
+    pub max_tool_output_bytes: Option<usize>,
+
+    /// File to read the base instructions from. Unset by default.
+    pub base_instructions_file: Option<PathBuf>,
 }
 
 impl ConfigToml {
@@ -359,6 +513,7 @@ pub struct ConfigOverrides {
     pub model_provider: Option<String>,
     pub config_profile: Option<String>,
     pub codex_linux_sandbox_exe: Option<PathBuf>,
+    pub rollout_dir: Option<PathBuf>,
 }
 
 impl Config {
@@ -380,6 +535,7 @@ impl Config {
             model_provider,
             config_profile: config_profile_key,
             codex_linux_sandbox_exe,
+            rollout_dir,
         } = overrides;
 
         let config_profile = match config_profile_key.as_ref().or(cfg.profile.as_ref()) {
@@ -457,6 +613,14 @@ impl Config {
 
         let experimental_resume = cfg.experimental_resume;
 
+        if cfg.model_max_reasoning_tokens == Some(0) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "model_max_reasoning_tokens must be greater than 0",
+            ));
+        }
+        let model_max_reasoning_tokens = cfg.model_max_reasoning_tokens;
+
         let config = Self {
             model,
             model_context_window,
@@ -486,10 +650,12 @@ impl Config {
             codex_linux_sandbox_exe,
 
             hide_agent_reasoning: cfg.hide_agent_reasoning.unwrap_or(false),
-            model_reasoning_effort: config_profile
-                .model_reasoning_effort
-                .or(cfg.model_reasoning_effort)
-                .unwrap_or_default(),
+            model_reasoning_effort: resolve_reasoning_effort(
+                config_profile
+                    .model_reasoning_effort
+                    .or(cfg.model_reasoning_effort),
+                cfg.model_reasoning_effort_strict.unwrap_or(false),
+            )?,
             model_reasoning_summary: config_profile
                 .model_reasoning_summary
                 .or(cfg.model_reasoning_summary)
@@ -505,6 +671,35 @@ impl Config {
                 .unwrap_or("https://chatgpt.com/backend-api/".to_string()),
 
             experimental_resume,
+            rollout_integrity_hash: cfg.rollout_integrity_hash.unwrap_or(false),
+            model_max_reasoning_tokens,
+            provider_fallback: cfg.provider_fallback.unwrap_or_default(),
+            rollout_persist_reasoning: cfg.rollout_persist_reasoning.unwrap_or(false),
+            rollout_gzip: cfg.rollout_gzip.unwrap_or(false),
+            rollout_dedupe_outputs: cfg.rollout_dedupe_outputs.unwrap_or(false),
+            model_reasoning_summary_language: cfg
+                .model_reasoning_summary_language
+                .filter(|lang| !lang.trim().is_empty()),
+            rollout_dir: rollout_dir.or(cfg.rollout_dir),
+            dedupe_images: cfg.dedupe_images.unwrap_or(false),
+            escalate_reasoning_on_empty: cfg.escalate_reasoning_on_empty.unwrap_or(false),
+            response_header_allowlist: cfg
+                .response_header_allowlist
+                .unwrap_or_else(default_response_header_allowlist),
+            strict_reasoning_include: cfg.strict_reasoning_include.unwrap_or(false),
+            rollout_utc_timestamps: cfg.rollout_utc_timestamps.unwrap_or(false),
+            collapse_tool_output_spaces: cfg.collapse_tool_output_spaces.unwrap_or(false).then(
+                || {
+                    cfg.collapse_tool_output_spaces_marker
+                        .unwrap_or_else(|| " ".to_string())
+                },
+            ),
+            rollout_max_bytes: cfg.rollout_max_bytes.filter(|max| *max > 0),
+            normalize_user_instructions: cfg.normalize_user_instructions.unwrap_or(false),
+            parallel_tool_calls: cfg.parallel_tool_calls,
+            max_tool_output_bytes: cfg.max_tool_output_bytes.filter(|max| *max > 0),
+            base_instructions_file: cfg.base_instructions_file,
+            image_uploader: None,
         };
         Ok(config)
     }
@@ -527,6 +722,56 @@ impl Config {
     }
 }
 
+/// Maps the configured reasoning effort onto a [`ReasoningEffort`]. Unknown
+/// values are rejected when `strict` is set and otherwise replaced by the
+/// default with a warning.
+fn resolve_reasoning_effort(
+    setting: Option<ReasoningEffortSetting>,
+    strict: bool,
+) -> std::io::Result<ReasoningEffort> {
+    match setting {
+        None => Ok(ReasoningEffort::default()),
+        Some(ReasoningEffortSetting::Known(effort)) => Ok(effort),
+        Some(ReasoningEffortSetting::Unknown(value)) => {
+            let valid = [
+                ReasoningEffort::Low,
+                ReasoningEffort::Medium,
+                ReasoningEffort::High,
+                ReasoningEffort::None,
+            ]
+            .map(|effort| format!("\"{effort}\""))
+            .join(", ");
+            if strict {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    format!("unknown model_reasoning_effort \"{value}\"; expected one of {valid}"),
+                ));
+            }
+            let default = ReasoningEffort::default();
+            tracing::warn!(
+                "unknown model_reasoning_effort \"{value}\" (expected one of {valid}); using \"{default}\""
+            );
+            Ok(default)
+        }
+    }
+}
+
+/// Rate-limit headers returned by OpenAI, captured unless
+/// `response_header_allowlist` says otherwise.
+fn default_response_header_allowlist() -> Vec<String> {
+    [
+        "x-ratelimit-limit-requests",
+        "x-ratelimit-limit-tokens",
+        "x-ratelimit-remaining-requests",
+        "x-ratelimit-remaining-tokens",
+        "x-ratelimit-reset-requests",
+        "x-ratelimit-reset-tokens",
+    ]
+    .into_iter()
+    .map(str::to_string)
+    .collect()
+}
+
 fn default_model() -> String {
     OPENAI_DEFAULT_MODEL.to_string()
 }
@@ -659,6 +904,71 @@ writable_roots = [
         );
     }
 
+    fn load_reasoning_effort(toml: &str) -> std::io::Result<ReasoningEffort> {
+        let cfg = toml::from_str::<ConfigToml>(toml).expect("TOML deserialization should succeed");
+        let codex_home = TempDir::new()?;
+        let config = Config::load_from_base_config_with_overrides(
+            cfg,
+            ConfigOverrides {
+                cwd: Some(codex_home.path().to_path_buf()),
+                ..Default::default()
+            },
+            codex_home.path().to_path_buf(),
+        )?;
+        Ok(config.model_reasoning_effort)
+    }
+
+    #[test]
+    fn test_known_reasoning_effort() -> std::io::Result<()> {
+        assert_eq!(
+            ReasoningEffort::High,
+            load_reasoning_effort(r#"model_reasoning_effort = "high""#)?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_unknown_reasoning_effort_falls_back_to_default() -> std::io::Result<()> {
+        assert_eq!(
+            ReasoningEffort::default(),
+            load_reasoning_effort(r#"model_reasoning_effort = "maximal""#)?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_zero_max_reasoning_tokens_is_rejected() {
+        let cfg = toml::from_str::<ConfigToml>("model_max_reasoning_tokens = 0")
+            .expect("TOML deserialization should succeed");
+        let codex_home = TempDir::new().unwrap();
+        let err = Config::load_from_base_config_with_overrides(
+            cfg,
+            ConfigOverrides {
+                cwd: Some(codex_home.path().to_path_buf()),
+                ..Default::default()
+            },
+            codex_home.path().to_path_buf(),
+        )
+        .expect_err("zero should be rejected");
+        assert_eq!(std::io::ErrorKind::InvalidInput, err.kind());
+    }
+
+    #[test]
+    fn test_unknown_reasoning_effort_is_rejected_in_strict_mode() {
+        let err = load_reasoning_effort(
+            r#"
+model_reasoning_effort = "maximal"
+model_reasoning_effort_strict = true
+"#,
+        )
+        .expect_err("strict mode should reject unknown values");
+        assert_eq!(std::io::ErrorKind::InvalidInput, err.kind());
+        assert_eq!(
+            r#"unknown model_reasoning_effort "maximal"; expected one of "low", "medium", "high", "none""#,
+            err.to_string()
+        );
+    }
+
     struct PrecedenceTestFixture {
         cwd: TempDir,
         codex_home: TempDir,
@@ -739,6 +1049,7 @@ disable_response_storage = true
             request_max_retries: Some(4),
             stream_max_retries: Some(10),
             stream_idle_timeout_ms: Some(300_000),
+            max_stop_sequences: None,
         };
         let model_provider_map = {
             let mut model_provider_map = built_in_model_providers();
@@ -818,6 +1129,26 @@ disable_response_storage = true
                 model_supports_reasoning_summaries: false,
                 chatgpt_base_url: "https://chatgpt.com/backend-api/".to_string(),
                 experimental_resume: None,
+                rollout_integrity_hash: false,
+                model_max_reasoning_tokens: None,
+                provider_fallback: Vec::new(),
+                rollout_persist_reasoning: false,
+                rollout_gzip: false,
+                rollout_dedupe_outputs: false,
+                model_reasoning_summary_language: None,
+                rollout_dir: None,
+                dedupe_images: false,
+                escalate_reasoning_on_empty: false,
+                response_header_allowlist: default_response_header_allowlist(),
+                strict_reasoning_include: false,
+                rollout_utc_timestamps: false,
+                collapse_tool_output_spaces: None,
+                rollout_max_bytes: None,
+                normalize_user_instructions: false,
+                parallel_tool_calls: None,
+                max_tool_output_bytes: None,
+                base_instructions_file: None,
+                image_uploader: None,
             },
             o3_profile_config
         );
@@ -865,6 +1196,26 @@ disable_response_storage = true
             model_supports_reasoning_summaries: false,
             chatgpt_base_url: "https://chatgpt.com/backend-api/".to_string(),
             experimental_resume: None,
+            rollout_integrity_hash: false,
+            model_max_reasoning_tokens: None,
+            provider_fallback: Vec::new(),
+            rollout_persist_reasoning: false,
+            rollout_gzip: false,
+            rollout_dedupe_outputs: false,
+            model_reasoning_summary_language: None,
+            rollout_dir: None,
+            dedupe_images: false,
+            escalate_reasoning_on_empty: false,
+            response_header_allowlist: default_response_header_allowlist(),
+            strict_reasoning_include: false,
+            rollout_utc_timestamps: false,
+            collapse_tool_output_spaces: None,
+            rollout_max_bytes: None,
+            normalize_user_instructions: false,
+            parallel_tool_calls: None,
+            max_tool_output_bytes: None,
+            base_instructions_file: None,
+            image_uploader: None,
         };
 
         assert_eq!(expected_gpt3_profile_config, gpt3_profile_config);
@@ -927,6 +1278,26 @@ disable_response_storage = true
             model_supports_reasoning_summaries: false,
             chatgpt_base_url: "https://chatgpt.com/backend-api/".to_string(),
             experimental_resume: None,
+            rollout_integrity_hash: false,
+            model_max_reasoning_tokens: None,
+            provider_fallback: Vec::new(),
+            rollout_persist_reasoning: false,
+            rollout_gzip: false,
+            rollout_dedupe_outputs: false,
+            model_reasoning_summary_language: None,
+            rollout_dir: None,
+            dedupe_images: false,
+            escalate_reasoning_on_empty: false,
+            response_header_allowlist: default_response_header_allowlist(),
+            strict_reasoning_include: false,
+            rollout_utc_timestamps: false,
+            collapse_tool_output_spaces: None,
+            rollout_max_bytes: None,
+            normalize_user_instructions: false,
+            parallel_tool_calls: None,
+            max_tool_output_bytes: None,
+            base_instructions_file: None,
+            image_uploader: None,
         };
 
         assert_eq!(expected_zdr_profile_config, zdr_profile_config);
@@ -1,6 +1,6 @@
 use serde::Deserialize;
 
-use crate::config_types::ReasoningEffort;
+use crate::config_types::ReasoningEffortSetting;
 use crate::config_types::ReasoningSummary;
 use crate::protocol::AskForApproval;
 
@@ -14,7 +14,7 @@ pub struct ConfigProfile {
     pub model_provider: Option<String>,
     pub approval_policy: Option<AskForApproval>,
     pub disable_response_storage: Option<bool>,
-    pub model_reasoning_effort: Option<ReasoningEffort>,
+    pub model_reasoning_effort: Option<ReasoningEffortSetting>,
     pub model_reasoning_summary: Option<ReasoningSummary>,
     pub chatgpt_base_url: Option<String>,
 }
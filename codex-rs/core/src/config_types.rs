@@ -214,6 +214,17 @@ pub enum ReasoningEffort {
     None,
 }
 
+/// `model_reasoning_effort` as written in `config.toml`. Values that do not
+/// name a [`ReasoningEffort`] (typos, or levels added by newer releases) are
+/// preserved so that config loading can decide whether to reject them or fall
+/// back to the default.
+#[derive(Debug, Deserialize, Clone, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum ReasoningEffortSetting {
+    Known(ReasoningEffort),
+    Unknown(String),
+}
+
 /// A summary of the reasoning performed by the model. This can be useful for
 /// debugging and understanding the model's reasoning process.
 /// See https://platform.openai.com/docs/guides/reasoning?api-mode=responses#reasoning-summaries
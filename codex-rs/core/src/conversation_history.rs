@@ -1,3 +1,10 @@
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+
+use sha2::Digest;
+use sha2::Sha256;
+
+use crate::models::ContentItem;
 use crate::models::ResponseItem;
 
 /// Transcript of conversation history that is needed:
@@ -37,6 +44,50 @@ impl ConversationHistory {
     }
 }
 
+/// Replaces every image that is identical to one earlier in `items` with a
+/// short text reference to the earlier attachment, so that an image attached
+/// in several turns is only sent once per request. Images are compared by a
+/// hash of their URL, which for local attachments is the base64 `data:` URL.
+pub(crate) fn dedupe_images(items: Vec<ResponseItem>) -> Vec<ResponseItem> {
+    let mut seen: HashMap<[u8; 32], usize> = HashMap::new();
+    items
+        .into_iter()
+        .map(|item| match item {
+            ResponseItem::Message {
+                role,
+                content,
+                name,
+            } => ResponseItem::Message {
+                role,
+                name,
+                content: content
+                    .into_iter()
+                    .map(|content_item| match content_item {
+                        ContentItem::InputImage { image_url, detail } => {
+                            let digest: [u8; 32] = Sha256::digest(image_url.as_bytes()).into();
+                            let next = seen.len() + 1;
+                            match seen.entry(digest) {
+                                Entry::Occupied(e) => ContentItem::InputText {
+                                    text: format!(
+                                        "[image {}: identical to an image attached earlier]",
+                                        e.get()
+                                    ),
+                                },
+                                Entry::Vacant(e) => {
+                                    e.insert(next);
+                                    ContentItem::InputImage { image_url, detail }
+                                }
+                            }
+                        }
+                        other => other,
+                    })
+                    .collect(),
+            },
+            other => other,
+        })
+        .collect()
+}
+
 /// Anything that is not a system message or "reasoning" message is considered
 /// an API message.
 fn is_api_message(message: &ResponseItem) -> bool {
@@ -48,3 +99,51 @@ fn is_api_message(message: &ResponseItem) -> bool {
         ResponseItem::Reasoning { .. } | ResponseItem::Other => false,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn user_message(content: Vec<ContentItem>) -> ResponseItem {
+        ResponseItem::Message {
+            role: "user".to_string(),
+            content,
+            name: None,
+        }
+    }
+
+    fn image(url: &str) -> ContentItem {
+        ContentItem::InputImage {
+            image_url: url.to_string(),
+            detail: None,
+        }
+    }
+
+    #[test]
+    fn repeated_image_is_sent_once() {
+        let logo = "data:image/png;base64,iVBORw0KGgo=";
+        let chart = "data:image/png;base64,R0lGODlhAQABAA==";
+        let items = vec![
+            user_message(vec![image(logo)]),
+            user_message(vec![image(chart), image(logo)]),
+        ];
+
+        let deduped = dedupe_images(items);
+
+        let expected = vec![
+            user_message(vec![image(logo)]),
+            user_message(vec![
+                image(chart),
+                ContentItem::InputText {
+                    text: "[image 1: identical to an image attached earlier]".to_string(),
+                },
+            ]),
+        ];
+        assert_eq!(
+            serde_json::to_value(&deduped).unwrap(),
+            serde_json::to_value(&expected).unwrap()
+        );
+    }
+}
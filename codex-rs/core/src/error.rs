@@ -1,6 +1,7 @@
 use reqwest::StatusCode;
 use serde_json;
 use std::io;
+use std::path::PathBuf;
 use thiserror::Error;
 use tokio::task::JoinError;
 
@@ -35,6 +36,47 @@ pub enum SandboxErr {
     LandlockRestrict,
 }
 
+/// Why a shell tool call's arguments were rejected before execution.
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum ShellParamError {
+    #[error("command must not be empty")]
+    EmptyCommand,
+
+    /// Arguments are passed to the OS as C strings, which cannot contain NUL.
+    #[error("command argument {index} contains a NUL byte")]
+    NulByte { index: usize },
+}
+
+/// Why a recorded session could not be read back. Parse failures carry the
+/// 1-based line number and a short snippet of the offending line.
+#[derive(Error, Debug)]
+pub enum RolloutReadError {
+    #[error("{}: session file not found", .path.display())]
+    NotFound { path: PathBuf },
+
+    #[error("{}:1: invalid session header: {source}", .path.display())]
+    BadHeader {
+        path: PathBuf,
+        source: serde_json::Error,
+    },
+
+    #[error("{}:{line}: invalid rollout line: {source}: {snippet}", .path.display())]
+    BadItem {
+        path: PathBuf,
+        line: usize,
+        snippet: String,
+        source: serde_json::Error,
+    },
+
+    /// A `function_call_output_ref` line whose output does not appear
+    /// earlier in the rollout.
+    #[error("{}:{line}: reference to an unknown function call output", .path.display())]
+    UnknownOutputRef { path: PathBuf, line: usize },
+
+    #[error("{}: {source}", .path.display())]
+    Io { path: PathBuf, source: io::Error },
+}
+
 #[derive(Error, Debug)]
 pub enum CodexErr {
     /// Returned by ResponsesClient when the SSE stream disconnects or errors out **after** the HTTP
@@ -77,6 +119,19 @@ pub enum CodexErr {
     #[error("codex-linux-sandbox was required but not provided")]
     LandlockSandboxExecutableNotProvided,
 
+    /// Returned when reasoning is requested without server-side storage but
+    /// `reasoning.encrypted_content` is not included, and
+    /// `strict_reasoning_include` forbids adding it.
+    #[error(
+        "reasoning requested with response storage disabled, but `include` lacks `reasoning.encrypted_content`"
+    )]
+    MissingReasoningInclude,
+
+    /// Returned when a [`crate::Prompt`] carries more stop sequences than the
+    /// provider's `max_stop_sequences`.
+    #[error("{requested} stop sequences requested but the provider accepts at most {max}")]
+    TooManyStopSequences { requested: usize, max: usize },
+
     // -----------------------------------------------------------------
     // Automatic conversions for common external error types
     // -----------------------------------------------------------------
@@ -12,6 +12,9 @@ env_flags! {
         value.parse().map(Duration::from_millis)
     };
 
+    /// Bytes of free-form content (tool arguments, commands) kept on spans.
+    pub CODEX_SPAN_CONTENT_LIMIT: usize = 64 * 1024, |value| value.parse();
+
     /// Fixture path for offline tests (see client.rs).
     pub CODEX_RS_SSE_FIXTURE: Option<&str> = None;
 }
@@ -0,0 +1,100 @@
+//! Git state of the session's working directory, recorded in the rollout
+//! header so a session can be matched to the code it ran against.
+
+use std::path::Path;
+
+use serde::Deserialize;
+use serde::Serialize;
+use tokio::process::Command;
+
+/// Git state of a working tree at session start.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GitInfo {
+    /// Checked-out branch, or `None` with a detached `HEAD`.
+    #[serde(default)]
+    pub branch: Option<String>,
+    /// Whether the tree had uncommitted changes, untracked files included. A
+    /// dirty session cannot be reproduced from the commit alone.
+    #[serde(default)]
+    pub is_dirty: bool,
+}
+
+/// Collects [`GitInfo`] for `cwd`. Returns `None` if `cwd` is not inside a
+/// Git work tree or `git` cannot be run.
+pub(crate) async fn collect_git_info(cwd: &Path) -> Option<GitInfo> {
+    if run_git(cwd, &["rev-parse", "--is-inside-work-tree"])
+        .await?
+        .trim()
+        != "true"
+    {
+        return None;
+    }
+    let branch = run_git(cwd, &["symbolic-ref", "--short", "-q", "HEAD"])
+        .await
+        .map(|branch| branch.trim().to_string())
+        .filter(|branch| !branch.is_empty());
+    let is_dirty = !run_git(cwd, &["status", "--porcelain"])
+        .await?
+        .trim()
+        .is_empty();
+    Some(GitInfo { branch, is_dirty })
+}
+
+/// Stdout of `git <args>` run in `cwd`, or `None` if it did not succeed.
+async fn run_git(cwd: &Path, args: &[&str]) -> Option<String> {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(cwd)
+        // Read-only queries should not contend for the index lock with
+        // whatever else is running in the repository.
+        .env("GIT_OPTIONAL_LOCKS", "0")
+        .output()
+        .await
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use tempfile::TempDir;
+
+    async fn git(dir: &Path, args: &[&str]) {
+        assert!(run_git(dir, args).await.is_some(), "git {args:?} failed");
+    }
+
+    #[tokio::test]
+    async fn reports_branch_and_dirty_state() {
+        let dir = TempDir::new().unwrap();
+        git(dir.path(), &["init", "-q"]).await;
+        git(dir.path(), &["symbolic-ref", "HEAD", "refs/heads/feature"]).await;
+
+        assert_eq!(
+            collect_git_info(dir.path()).await,
+            Some(GitInfo {
+                branch: Some("feature".to_string()),
+                is_dirty: false,
+            })
+        );
+
+        std::fs::write(dir.path().join("notes.txt"), "wip").unwrap();
+        assert_eq!(
+            collect_git_info(dir.path()).await,
+            Some(GitInfo {
+                branch: Some("feature".to_string()),
+                is_dirty: true,
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn non_git_directory_has_no_git_info() {
+        let dir = TempDir::new().unwrap();
+        assert_eq!(collect_git_info(dir.path()).await, None);
+    }
+}
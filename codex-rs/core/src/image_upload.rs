@@ -0,0 +1,124 @@
+//! Optional hosting of local images.
+//!
+//! By default a [`InputItem::LocalImage`] is read from disk and inlined into
+//! the request as a base64 `data:` URL. Deployments that would rather not
+//! resend every screenshot in full can set an [`ImageUploader`] on
+//! [`Config::image_uploader`]; local images are then uploaded once and
+//! referenced by the URL it returns. Images that fail to upload are still
+//! inlined.
+//!
+//! [`Config::image_uploader`]: crate::config::Config::image_uploader
+
+use std::fmt;
+use std::future::Future;
+use std::path::Path;
+use std::pin::Pin;
+
+use tracing::warn;
+
+use crate::protocol::InputItem;
+
+/// Future returned by [`ImageUploader::upload`].
+pub type UploadFuture<'a> = Pin<Box<dyn Future<Output = std::io::Result<String>> + Send + 'a>>;
+
+/// Makes a local image available to the model provider under a URL.
+pub trait ImageUploader: Send + Sync {
+    /// Uploads the image at `path` and returns a URL the provider can fetch
+    /// it from.
+    fn upload<'a>(&'a self, path: &'a Path) -> UploadFuture<'a>;
+}
+
+// `Config` is `Debug` and `PartialEq`; uploaders are opaque and compare by
+// identity.
+impl fmt::Debug for dyn ImageUploader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ImageUploader")
+    }
+}
+
+impl PartialEq for dyn ImageUploader {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::addr_eq(self, other)
+    }
+}
+
+/// Replaces every [`InputItem::LocalImage`] in `items` with an
+/// [`InputItem::Image`] pointing at the URL `uploader` returns for it. Images
+/// that fail to upload are left in place so they are inlined as before.
+pub(crate) async fn upload_local_images(
+    items: Vec<InputItem>,
+    uploader: Option<&dyn ImageUploader>,
+) -> Vec<InputItem> {
+    let Some(uploader) = uploader else {
+        return items;
+    };
+    let mut uploaded = Vec::with_capacity(items.len());
+    for item in items {
+        match item {
+            InputItem::LocalImage { path, detail } => match uploader.upload(&path).await {
+                Ok(image_url) => uploaded.push(InputItem::Image { image_url, detail }),
+                Err(e) => {
+                    warn!(
+                        "failed to upload image {}, sending it inline: {e}",
+                        path.display()
+                    );
+                    uploaded.push(InputItem::LocalImage { path, detail });
+                }
+            },
+            item => uploaded.push(item),
+        }
+    }
+    uploaded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::path::PathBuf;
+
+    /// Hosts every image at the same URL, except for paths containing
+    /// `offline`, which fail.
+    struct StubUploader;
+
+    impl ImageUploader for StubUploader {
+        fn upload<'a>(&'a self, path: &'a Path) -> UploadFuture<'a> {
+            Box::pin(async move {
+                if path.to_string_lossy().contains("offline") {
+                    return Err(std::io::Error::other("upload failed"));
+                }
+                Ok("https://images.example.com/abc.png".to_string())
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn local_images_are_replaced_with_uploaded_urls() {
+        let items = vec![
+            InputItem::Text {
+                text: "what is wrong here?".to_string(),
+            },
+            InputItem::LocalImage {
+                path: PathBuf::from("shot.png"),
+                detail: None,
+            },
+            InputItem::LocalImage {
+                path: PathBuf::from("offline.png"),
+                detail: None,
+            },
+        ];
+
+        assert_eq!(
+            upload_local_images(items.clone(), Some(&StubUploader)).await,
+            vec![
+                items[0].clone(),
+                InputItem::Image {
+                    image_url: "https://images.example.com/abc.png".to_string(),
+                    detail: None,
+                },
+                items[2].clone(),
+            ]
+        );
+        assert_eq!(upload_local_images(items.clone(), None).await, items);
+    }
+}
@@ -7,6 +7,7 @@
 
 mod chat_completions;
 mod client;
+pub use client::ModelClient;
 mod client_common;
 pub mod codex;
 pub use codex::Codex;
@@ -15,10 +16,16 @@ pub mod config;
 pub mod config_profile;
 pub mod config_types;
 mod conversation_history;
+mod conversation_tracing;
 pub mod error;
 pub mod exec;
 pub mod exec_env;
 mod flags;
+mod git_info;
+pub use git_info::GitInfo;
+mod image_upload;
+pub use image_upload::ImageUploader;
+pub use image_upload::UploadFuture;
 mod is_safe_command;
 mod mcp_connection_manager;
 mod mcp_tool_call;
@@ -26,7 +33,7 @@ mod message_history;
 mod model_provider_info;
 pub use model_provider_info::ModelProviderInfo;
 pub use model_provider_info::WireApi;
-mod models;
+pub mod models;
 pub mod openai_api_key;
 mod openai_model_info;
 mod openai_tools;
@@ -34,7 +41,35 @@ mod project_doc;
 pub mod protocol;
 mod rollout;
 mod safety;
+mod session_export;
+pub mod telemetry;
 mod user_notification;
 pub mod util;
 
+pub use client_common::IncludeOption;
+pub use client_common::InterruptibleOutput;
+pub use client_common::Prompt;
+pub use client_common::ResponseEvent;
+pub use client_common::ResponseStream;
+pub use client_common::SentenceChunks;
+pub use client_common::consume_interruptible;
 pub use client_common::model_supports_reasoning_summaries;
+pub use conversation_tracing::register_secret_pattern;
+pub use conversation_tracing::set_span_content_limit;
+pub use models::MessageBuilder;
+pub use openai_model_info::remaining_context;
+pub use openai_tools::Tool;
+pub use rollout::Clock;
+pub use rollout::RolloutRecorder;
+pub use rollout::RolloutSink;
+pub use rollout::SessionMeta;
+pub use rollout::SessionSummary;
+pub use rollout::SessionSummaryFooter;
+pub use rollout::SinkFuture;
+pub use rollout::SystemClock;
+pub use rollout::TurnTiming;
+pub use rollout::latest_session;
+pub use rollout::verify_rollout_integrity;
+pub use session_export::export_finetune_jsonl;
+pub use session_export::export_html;
+pub use session_export::export_markdown;
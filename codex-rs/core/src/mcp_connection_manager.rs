@@ -7,7 +7,6 @@
 //! `"<server><MCP_TOOL_NAME_DELIMITER><tool>"` as the key.
 
 use std::collections::HashMap;
-use std::collections::HashSet;
 use std::time::Duration;
 
 use anyhow::Context;
@@ -42,32 +41,70 @@ const LIST_TOOLS_TIMEOUT: Duration = Duration::from_secs(10);
 /// spawned successfully.
 pub type ClientStartErrors = HashMap<String, anyhow::Error>;
 
-fn qualify_tools(tools: Vec<ToolInfo>) -> HashMap<String, ToolInfo> {
-    let mut used_names = HashSet::new();
-    let mut qualified_tools = HashMap::new();
-    for tool in tools {
-        let mut qualified_name = format!(
-            "{}{}{}",
-            tool.server_name, MCP_TOOL_NAME_DELIMITER, tool.tool_name
-        );
-        if qualified_name.len() > MAX_TOOL_NAME_LENGTH {
-            let mut hasher = Sha1::new();
-            hasher.update(qualified_name.as_bytes());
-            let sha1 = hasher.finalize();
-            let sha1_str = format!("{sha1:x}");
+/// Name under which `server`'s tool `tool` is reported to the model:
+/// `"<server><MCP_TOOL_NAME_DELIMITER><tool>"`, with its end replaced by a
+/// SHA-1 if that is longer than [`MAX_TOOL_NAME_LENGTH`].
+///
+/// Distinct pairs can share a name, e.g. `a`/`b__c` and `a__b`/`c`;
+/// [`qualify_tools`] resolves such collisions.
+pub(crate) fn qualify_tool_name(server: &str, tool: &str) -> String {
+    let qualified_name = format!("{server}{MCP_TOOL_NAME_DELIMITER}{tool}");
+    if qualified_name.len() > MAX_TOOL_NAME_LENGTH {
+        with_hash_suffix(&qualified_name, &qualified_name)
+    } else {
+        qualified_name
+    }
+}
 
-            // Truncate to make room for the hash suffix
-            let prefix_len = MAX_TOOL_NAME_LENGTH - sha1_str.len();
+/// Appends the SHA-1 of `key` to `name`, truncating `name` to make room so
+/// the result is at most [`MAX_TOOL_NAME_LENGTH`] long.
+fn with_hash_suffix(name: &str, key: &str) -> String {
+    let mut hasher = Sha1::new();
+    hasher.update(key.as_bytes());
+    let sha1 = hasher.finalize();
+    let sha1_str = format!("{sha1:x}");
+
+    // Truncate to make room for the hash suffix, without splitting a
+    // character of a non-ASCII tool name.
+    let max_prefix_len = (MAX_TOOL_NAME_LENGTH - sha1_str.len()).min(name.len());
+    let prefix_len = (0..=max_prefix_len)
+        .rev()
+        .find(|&i| name.is_char_boundary(i))
+        .unwrap_or(0);
+
+    format!("{}{}", &name[..prefix_len], sha1_str)
+}
 
-            qualified_name = format!("{}{}", &qualified_name[..prefix_len], sha1_str);
+/// Keys `tools` by [`qualify_tool_name`]. When two tools from different
+/// servers or with different names qualify to the same name, the one that
+/// sorts later by (server, tool) is reported with a hash of both appended
+/// instead, so the outcome does not depend on which server answered first.
+/// Exact duplicates are skipped.
+fn qualify_tools(mut tools: Vec<ToolInfo>) -> HashMap<String, ToolInfo> {
+    tools.sort_by(|a, b| (&a.server_name, &a.tool_name).cmp(&(&b.server_name, &b.tool_name)));
+    let mut qualified_tools: HashMap<String, ToolInfo> = HashMap::new();
+    for tool in tools {
+        let mut qualified_name = qualify_tool_name(&tool.server_name, &tool.tool_name);
+        if let Some(existing) = qualified_tools.get(&qualified_name) {
+            if existing.server_name == tool.server_name && existing.tool_name == tool.tool_name {
+                warn!("skipping duplicated tool {}", qualified_name);
+                continue;
+            }
+            let disambiguated = with_hash_suffix(
+                &qualified_name,
+                &format!("{}/{}", tool.server_name, tool.tool_name),
+            );
+            warn!(
+                "tool `{}/{}` collides with `{}/{}` as {qualified_name}; reporting it as {disambiguated}",
+                tool.server_name, tool.tool_name, existing.server_name, existing.tool_name,
+            );
+            qualified_name = disambiguated;
         }
 
-        if used_names.contains(&qualified_name) {
+        if qualified_tools.contains_key(&qualified_name) {
             warn!("skipping duplicated tool {}", qualified_name);
             continue;
         }
-
-        used_names.insert(qualified_name.clone());
         qualified_tools.insert(qualified_name, tool);
     }
 
@@ -327,6 +364,77 @@ mod tests {
         assert!(qualified_tools.contains_key("server1__duplicate_tool"));
     }
 
+    #[test]
+    fn test_qualify_tool_name_same_tool_on_two_servers() {
+        assert_eq!(qualify_tool_name("github", "search"), "github__search");
+        assert_eq!(qualify_tool_name("jira", "search"), "jira__search");
+    }
+
+    #[test]
+    fn test_qualify_tools_collision_across_servers() {
+        let tools = || vec![create_test_tool("a", "b__c"), create_test_tool("a__b", "c")];
+
+        let qualified_tools = qualify_tools(tools());
+        let mut reversed = tools();
+        reversed.reverse();
+        let reversed_tools = qualify_tools(reversed);
+
+        let mut names: Vec<(&str, &str, &str)> = qualified_tools
+            .iter()
+            .map(|(name, tool)| {
+                (
+                    name.as_str(),
+                    tool.server_name.as_str(),
+                    tool.tool_name.as_str(),
+                )
+            })
+            .collect();
+        names.sort();
+        assert_eq!(
+            names,
+            vec![
+                ("a__b__c", "a", "b__c"),
+                (
+                    "a__b__c4adbd34ff868819408d2210e6788fd1f4dab1414",
+                    "a__b",
+                    "c"
+                ),
+            ]
+        );
+
+        let mut reversed_names: Vec<&String> = reversed_tools.keys().collect();
+        reversed_names.sort();
+        assert_eq!(
+            reversed_names,
+            names.iter().map(|(name, ..)| name).collect::<Vec<_>>()
+        );
+        assert_eq!(reversed_tools["a__b__c"].server_name, "a");
+    }
+
+    #[test]
+    fn test_qualify_tools_multibyte_names_are_cut_at_char_boundaries() {
+        // Byte 24, where the hash suffix starts, falls inside an `é`.
+        let tool_name = format!("x{}", "é".repeat(15));
+        let tools = vec![
+            create_test_tool("a", &format!("b__{tool_name}")),
+            create_test_tool("a__b", &tool_name),
+        ];
+
+        let qualified_tools = qualify_tools(tools);
+
+        let disambiguated = qualified_tools
+            .iter()
+            .find(|(_, tool)| tool.server_name == "a__b")
+            .map(|(name, _)| name.as_str())
+            .unwrap();
+        assert!(disambiguated.starts_with(&format!("a__b__x{}", "é".repeat(8))));
+        assert_eq!(disambiguated.len(), 63);
+
+        let long = qualify_tool_name("a", &"é".repeat(40));
+        assert!(long.starts_with(&format!("a__{}", "é".repeat(10))));
+        assert!(long.len() <= MAX_TOOL_NAME_LENGTH);
+    }
+
     #[test]
     fn test_qualify_tools_long_names_same_server() {
         let server_name = "my_server";
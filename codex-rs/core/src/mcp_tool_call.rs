@@ -1,8 +1,10 @@
 use std::time::Duration;
 
+use tracing::Instrument;
 use tracing::error;
 
 use crate::codex::Session;
+use crate::conversation_tracing::create_mcp_tool_call_span;
 use crate::models::FunctionCallOutputPayload;
 use crate::models::ResponseInputItem;
 use crate::protocol::Event;
@@ -35,6 +37,8 @@ pub(crate) async fn handle_mcp_tool_call(
                     output: FunctionCallOutputPayload {
                         content: format!("err: {e}"),
                         success: Some(false),
+                        exit_code: None,
+                        duration_ms: None,
                     },
                 };
             }
@@ -50,8 +54,10 @@ pub(crate) async fn handle_mcp_tool_call(
     notify_mcp_tool_call_event(sess, sub_id, tool_call_begin_event).await;
 
     // Perform the tool call.
+    let span = create_mcp_tool_call_span(&sess.trace_context(), &server, &tool_name, &arguments);
     let result = sess
         .call_tool(&server, &tool_name, arguments_value, timeout)
+        .instrument(span)
         .await
         .map_err(|e| format!("tool call error: {e}"));
     let tool_call_end_event = EventMsg::McpToolCallEnd(McpToolCallEndEvent {
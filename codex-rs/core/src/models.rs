@@ -1,19 +1,29 @@
 use std::collections::HashMap;
+use std::path::PathBuf;
 
 use base64::Engine;
 use mcp_types::CallToolResult;
+use mcp_types::ContentBlock;
+use mcp_types::EmbeddedResourceResource;
 use serde::Deserialize;
+use serde::Deserializer;
 use serde::Serialize;
 use serde::ser::Serializer;
 
+use crate::error::ShellParamError;
+use crate::protocol::ImageDetail;
 use crate::protocol::InputItem;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum ResponseInputItem {
     Message {
         role: String,
         content: Vec<ContentItem>,
+        /// Participant name, e.g. the tool a `tool` message answers for.
+        /// Omitted from the serialized message when unset.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        name: Option<String>,
     },
     FunctionCallOutput {
         call_id: String,
@@ -25,20 +35,32 @@ pub enum ResponseInputItem {
     },
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum ContentItem {
-    InputText { text: String },
-    InputImage { image_url: String },
-    OutputText { text: String },
+    InputText {
+        text: String,
+    },
+    InputImage {
+        image_url: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        detail: Option<ImageDetail>,
+    },
+    OutputText {
+        text: String,
+    },
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum ResponseItem {
     Message {
         role: String,
         content: Vec<ContentItem>,
+        /// Participant name, e.g. the tool a `tool` message answers for.
+        /// Omitted from the serialized message when unset.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        name: Option<String>,
     },
     Reasoning {
         id: String,
@@ -78,28 +100,120 @@ pub enum ResponseItem {
 impl From<ResponseInputItem> for ResponseItem {
     fn from(item: ResponseInputItem) -> Self {
         match item {
-            ResponseInputItem::Message { role, content } => Self::Message { role, content },
+            ResponseInputItem::Message {
+                role,
+                content,
+                name,
+            } => Self::Message {
+                role,
+                content,
+                name,
+            },
             ResponseInputItem::FunctionCallOutput { call_id, output } => {
                 Self::FunctionCallOutput { call_id, output }
             }
             ResponseInputItem::McpToolCallOutput { call_id, result } => Self::FunctionCallOutput {
                 call_id,
-                output: FunctionCallOutputPayload {
-                    success: Some(result.is_ok()),
-                    content: result.map_or_else(
-                        |tool_call_err| format!("err: {tool_call_err:?}"),
-                        |result| {
-                            serde_json::to_string(&result)
-                                .unwrap_or_else(|e| format!("JSON serialization error: {e}"))
-                        },
-                    ),
-                },
+                output: mcp_tool_call_output(result).0,
             },
         }
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl ResponseInputItem {
+    /// Expands this input into the items sent to the model. Identical to the
+    /// `From` conversion, except that images returned by an MCP tool (which a
+    /// function call output cannot carry) follow the output as a user message.
+    pub(crate) fn into_response_items(self) -> Vec<ResponseItem> {
+        match self {
+            ResponseInputItem::McpToolCallOutput { call_id, result } => {
+                let (output, images) = mcp_tool_call_output(result);
+                let mut items = vec![ResponseItem::FunctionCallOutput { call_id, output }];
+                if !images.is_empty() {
+                    items.push(ResponseItem::Message {
+                        role: "user".to_string(),
+                        content: images,
+                        name: None,
+                    });
+                }
+                items
+            }
+            other => vec![other.into()],
+        }
+    }
+}
+
+/// Maps the content blocks of an MCP tool result onto model inputs: text
+/// blocks become the function output text, images are returned separately as
+/// `InputImage`s (referenced by placeholder in the text), and resources become
+/// textual references. `success` reflects both transport errors and the
+/// tool's own `isError` flag.
+fn mcp_tool_call_output(
+    result: Result<CallToolResult, String>,
+) -> (FunctionCallOutputPayload, Vec<ContentItem>) {
+    let CallToolResult {
+        content,
+        is_error,
+        structured_content,
+    } = match result {
+        Ok(result) => result,
+        Err(e) => {
+            let output = FunctionCallOutputPayload {
+                content: format!("err: {e}"),
+                success: Some(false),
+                exit_code: None,
+                duration_ms: None,
+            };
+            return (output, Vec::new());
+        }
+    };
+
+    let mut parts = Vec::with_capacity(content.len());
+    let mut images = Vec::new();
+    for block in content {
+        match block {
+            ContentBlock::TextContent(text) => parts.push(text.text),
+            ContentBlock::ImageContent(image) => {
+                images.push(ContentItem::InputImage {
+                    image_url: format!("data:{};base64,{}", image.mime_type, image.data),
+                    detail: None,
+                });
+                parts.push(format!("[image {}: {}]", images.len(), image.mime_type));
+            }
+            ContentBlock::AudioContent(audio) => {
+                parts.push(format!("[audio omitted: {}]", audio.mime_type));
+            }
+            ContentBlock::ResourceLink(link) => {
+                parts.push(format!("[resource: {}]({})", link.name, link.uri));
+            }
+            ContentBlock::EmbeddedResource(embedded) => match embedded.resource {
+                EmbeddedResourceResource::TextResourceContents(resource) => {
+                    parts.push(resource.text);
+                }
+                EmbeddedResourceResource::BlobResourceContents(resource) => {
+                    parts.push(format!("[resource: {}]", resource.uri));
+                }
+            },
+        }
+    }
+
+    // Tools that only return structured content still need to say something.
+    if parts.is_empty() {
+        if let Some(structured) = structured_content {
+            parts.push(structured.to_string());
+        }
+    }
+
+    let output = FunctionCallOutputPayload {
+        content: parts.join("\n"),
+        success: Some(!is_error.unwrap_or(false)),
+        exit_code: None,
+        duration_ms: None,
+    };
+    (output, images)
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum LocalShellStatus {
     Completed,
@@ -107,13 +221,13 @@ pub enum LocalShellStatus {
     Incomplete,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum LocalShellAction {
     Exec(LocalShellExecAction),
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct LocalShellExecAction {
     pub command: Vec<String>,
     pub timeout_ms: Option<u64>,
@@ -122,7 +236,7 @@ pub struct LocalShellExecAction {
     pub user: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum ReasoningItemReasoningSummary {
     SummaryText { text: String },
@@ -136,8 +250,10 @@ impl From<Vec<InputItem>> for ResponseInputItem {
                 .into_iter()
                 .filter_map(|c| match c {
                     InputItem::Text { text } => Some(ContentItem::InputText { text }),
-                    InputItem::Image { image_url } => Some(ContentItem::InputImage { image_url }),
-                    InputItem::LocalImage { path } => match std::fs::read(&path) {
+                    InputItem::Image { image_url, detail } => {
+                        Some(ContentItem::InputImage { image_url, detail })
+                    }
+                    InputItem::LocalImage { path, detail } => match std::fs::read(&path) {
                         Ok(bytes) => {
                             let mime = mime_guess::from_path(&path)
                                 .first()
@@ -146,6 +262,7 @@ impl From<Vec<InputItem>> for ResponseInputItem {
                             let encoded = base64::engine::general_purpose::STANDARD.encode(bytes);
                             Some(ContentItem::InputImage {
                                 image_url: format!("data:{mime};base64,{encoded}"),
+                                detail,
                             })
                         }
                         Err(err) => {
@@ -159,17 +276,80 @@ impl From<Vec<InputItem>> for ResponseInputItem {
                     },
                 })
                 .collect::<Vec<ContentItem>>(),
+            name: None,
         }
     }
 }
 
+/// Fluent construction of a user message, as an alternative to assembling
+/// [`InputItem`]s by hand:
+///
+/// ```ignore
+/// let message = MessageBuilder::new()
+///     .text("What is wrong with this layout?")
+///     .image_path("screenshot.png")
+///     .build();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct MessageBuilder {
+    items: Vec<InputItem>,
+}
+
+impl MessageBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn text(mut self, text: impl Into<String>) -> Self {
+        self.items.push(InputItem::Text { text: text.into() });
+        self
+    }
+
+    /// Attaches an image by URL, which may be a `data:` URL.
+    pub fn image_url(mut self, image_url: impl Into<String>) -> Self {
+        self.items.push(InputItem::Image {
+            image_url: image_url.into(),
+            detail: None,
+        });
+        self
+    }
+
+    /// Attaches a local image. The file is read when the message is built.
+    pub fn image_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.items.push(InputItem::LocalImage {
+            path: path.into(),
+            detail: None,
+        });
+        self
+    }
+
+    /// The items added so far, e.g. for `Op::UserInput`.
+    pub fn into_items(self) -> Vec<InputItem> {
+        self.items
+    }
+
+    /// Builds the user message, converting the items the same way as
+    /// `ResponseInputItem::from(Vec<InputItem>)`.
+    pub fn build(self) -> ResponseInputItem {
+        ResponseInputItem::from(self.items)
+    }
+}
+
 /// If the `name` of a `ResponseItem::FunctionCall` is either `container.exec`
 /// or shell`, the `arguments` field should deserialize to this struct.
 #[derive(Deserialize, Debug, Clone, PartialEq)]
 pub struct ShellToolCallParams {
+    /// Accepts an argv array or, as some models emit, a single shell string;
+    /// see [`deserialize_command`].
+    #[serde(deserialize_with = "deserialize_command")]
     pub command: Vec<String>,
     pub workdir: Option<String>,
 
+    /// Environment variables to set for the command, on top of the ones
+    /// derived from the shell environment policy.
+    #[serde(default)]
+    pub env: Option<HashMap<String, String>>,
+
     /// This is the maximum time in seconds that the command is allowed to run.
     #[serde(rename = "timeout")]
     // The wire format uses `timeout`, which has ambiguous units, so we use
@@ -177,11 +357,47 @@ pub struct ShellToolCallParams {
     pub timeout_ms: Option<u64>,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+/// Deserializes `command` from an argv array as-is, or from a single string
+/// by running it through `bash -lc`.
+fn deserialize_command<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Wire {
+        Argv(Vec<String>),
+        Shell(String),
+    }
+
+    Ok(match Wire::deserialize(deserializer)? {
+        Wire::Argv(argv) => argv,
+        Wire::Shell(script) => vec!["bash".to_string(), "-lc".to_string(), script],
+    })
+}
+
+impl ShellToolCallParams {
+    /// Rejects commands that could never be executed, so the model gets a
+    /// clear error instead of a confusing one from the spawn.
+    pub fn validate(&self) -> Result<(), ShellParamError> {
+        if self.command.is_empty() {
+            return Err(ShellParamError::EmptyCommand);
+        }
+        match self.command.iter().position(|arg| arg.contains('\0')) {
+            Some(index) => Err(ShellParamError::NulByte { index }),
+            None => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct FunctionCallOutputPayload {
     pub content: String,
-    #[expect(dead_code)]
     pub success: Option<bool>,
+    /// Exit code of the shell command that produced `content`, if any.
+    pub exit_code: Option<i32>,
+    /// How long the shell command that produced `content` ran.
+    pub duration_ms: Option<u64>,
 }
 
 // The Responses API expects two *different* shapes depending on success vs failure:
@@ -201,10 +417,59 @@ impl Serialize for FunctionCallOutputPayload {
         // form `{ content, success:false }` triggers the 400 we are still seeing. Mirror the JS CLI
         // exactly: always emit a bare string.
 
+        // For the same reason `exit_code` and `duration_ms` never go over the
+        // wire: they are for local consumers only, and the model already sees
+        // both in the formatted `content`. Rollouts keep them (and `success`)
+        // through their own representation of function call outputs.
+
         serializer.serialize_str(&self.content)
     }
 }
 
+// Since `output` is serialized as a bare string (see above), some rollouts
+// contain that form, while older builds and current rollouts write the full
+// object. Accept both.
+impl<'de> Deserialize<'de> for FunctionCallOutputPayload {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Wire {
+            Content(String),
+            Object {
+                content: String,
+                success: Option<bool>,
+                #[serde(default)]
+                exit_code: Option<i32>,
+                #[serde(default)]
+                duration_ms: Option<u64>,
+            },
+        }
+
+        Ok(match Wire::deserialize(deserializer)? {
+            Wire::Content(content) => FunctionCallOutputPayload {
+                content,
+                success: None,
+                exit_code: None,
+                duration_ms: None,
+            },
+            Wire::Object {
+                content,
+                success,
+                exit_code,
+                duration_ms,
+            } => FunctionCallOutputPayload {
+                content,
+                success,
+                exit_code,
+                duration_ms,
+            },
+        })
+    }
+}
+
 // Implement Display so callers can treat the payload like a plain string when logging or doing
 // trivial substring checks in tests (existing tests call `.contains()` on the output). Display
 // returns the raw `content` field.
@@ -222,11 +487,352 @@ impl std::ops::Deref for FunctionCallOutputPayload {
     }
 }
 
+impl FunctionCallOutputPayload {
+    /// Replaces each run of three or more spaces in `content` with
+    /// `replacement`, e.g. to shrink padded tables. Line breaks and the
+    /// leading indentation of each line are kept, so `content` must be raw
+    /// text rather than JSON with escaped line breaks.
+    pub(crate) fn collapse_space_runs(content: &str, replacement: &str) -> String {
+        let mut collapsed = String::with_capacity(content.len());
+        for line in content.split_inclusive('\n') {
+            let body = line.trim_start_matches([' ', '\t']);
+            collapsed.push_str(&line[..line.len() - body.len()]);
+
+            let mut spaces = 0;
+            for c in body.chars() {
+                if c == ' ' {
+                    spaces += 1;
+                    continue;
+                }
+                push_spaces(&mut collapsed, spaces, replacement);
+                spaces = 0;
+                collapsed.push(c);
+            }
+            push_spaces(&mut collapsed, spaces, replacement);
+        }
+        collapsed
+    }
+
+    /// Returns `content` unchanged if it is at most `limit` bytes. Otherwise
+    /// keeps roughly `limit / 2` bytes from each end, so that both the start of
+    /// the output and its final lines (often the error) survive, and replaces
+    /// the middle with a `… [N bytes truncated] …` marker.
+    pub(crate) fn truncated(content: String, limit: usize) -> String {
+        if content.len() <= limit {
+            return content;
+        }
+        let mut head_end = limit / 2;
+        while !content.is_char_boundary(head_end) {
+            head_end -= 1;
+        }
+        let mut tail_start = content.len() - (limit - limit / 2);
+        while !content.is_char_boundary(tail_start) {
+            tail_start += 1;
+        }
+        format!(
+            "{}… [{} bytes truncated] …{}",
+            &content[..head_end],
+            tail_start - head_end,
+            &content[tail_start..]
+        )
+    }
+}
+
+fn push_spaces(out: &mut String, count: usize, replacement: &str) {
+    if count >= 3 {
+        out.push_str(replacement);
+    } else {
+        out.extend(std::iter::repeat_n(' ', count));
+    }
+}
+
 #[cfg(test)]
 mod tests {
     #![allow(clippy::unwrap_used)]
     use super::*;
 
+    #[test]
+    fn padded_output_space_runs_are_collapsed() {
+        let content = "NAME        READY   STATUS\n    web-1       1/1     Running   \n";
+        assert_eq!(
+            FunctionCallOutputPayload::collapse_space_runs(content, " "),
+            "NAME READY STATUS\n    web-1 1/1 Running \n"
+        );
+        assert_eq!(
+            FunctionCallOutputPayload::collapse_space_runs("a    b", " … "),
+            "a … b"
+        );
+    }
+
+    #[test]
+    fn short_output_is_not_truncated() {
+        let content = "line 1\nline 2\n".to_string();
+        assert_eq!(
+            FunctionCallOutputPayload::truncated(content.clone(), content.len()),
+            content
+        );
+    }
+
+    #[test]
+    fn long_output_keeps_head_and_tail() {
+        let content = format!("HEAD{}TAIL", "x".repeat(1000));
+        assert_eq!(
+            FunctionCallOutputPayload::truncated(content, 10),
+            "HEADx… [998 bytes truncated] …xTAIL"
+        );
+
+        // Multibyte characters are never split.
+        let truncated = FunctionCallOutputPayload::truncated("ééééé".to_string(), 5);
+        assert_eq!(truncated, "é… [6 bytes truncated] …é");
+    }
+
+    #[test]
+    fn normal_output_is_unchanged_by_space_collapsing() {
+        let content = "fn main() {\n    let x = 1;  // two spaces\n\tprintln!(\"{x}\");\n}\n";
+        assert_eq!(
+            FunctionCallOutputPayload::collapse_space_runs(content, " "),
+            content
+        );
+    }
+
+    #[test]
+    fn equal_messages_compare_equal() {
+        let message = || ResponseItem::Message {
+            role: "assistant".to_string(),
+            content: vec![ContentItem::OutputText {
+                text: "done".to_string(),
+            }],
+            name: None,
+        };
+        assert_eq!(message(), message());
+        assert_ne!(
+            message(),
+            ResponseItem::Message {
+                role: "user".to_string(),
+                content: vec![ContentItem::OutputText {
+                    text: "done".to_string(),
+                }],
+                name: None,
+            }
+        );
+        assert_eq!(
+            MessageBuilder::new().text("hi").build(),
+            ResponseInputItem::Message {
+                role: "user".to_string(),
+                content: vec![ContentItem::InputText {
+                    text: "hi".to_string(),
+                }],
+                name: None,
+            }
+        );
+    }
+
+    #[test]
+    fn image_detail_is_serialized_only_when_set() {
+        let image = |detail| {
+            serde_json::to_value(ResponseItem::from(ResponseInputItem::from(vec![
+                InputItem::Image {
+                    image_url: "https://example.com/a.png".to_string(),
+                    detail,
+                },
+            ])))
+            .unwrap()
+        };
+
+        assert_eq!(
+            image(None),
+            serde_json::json!({
+                "type": "message",
+                "role": "user",
+                "content": [{"type": "input_image", "image_url": "https://example.com/a.png"}],
+            })
+        );
+        for (detail, wire) in [
+            (ImageDetail::Low, "low"),
+            (ImageDetail::High, "high"),
+            (ImageDetail::Auto, "auto"),
+        ] {
+            assert_eq!(image(Some(detail))["content"][0]["detail"], wire);
+        }
+    }
+
+    #[test]
+    fn message_name_is_serialized_only_when_set() {
+        let message = |name: Option<&str>| {
+            serde_json::to_value(ResponseItem::Message {
+                role: "tool".to_string(),
+                content: vec![ContentItem::InputText {
+                    text: "42".to_string(),
+                }],
+                name: name.map(str::to_string),
+            })
+            .unwrap()
+        };
+
+        assert_eq!(
+            message(Some("calculator")),
+            serde_json::json!({
+                "type": "message",
+                "role": "tool",
+                "content": [{"type": "input_text", "text": "42"}],
+                "name": "calculator",
+            })
+        );
+        assert_eq!(
+            message(None),
+            serde_json::json!({
+                "type": "message",
+                "role": "tool",
+                "content": [{"type": "input_text", "text": "42"}],
+            })
+        );
+    }
+
+    #[test]
+    fn message_builder_matches_manual_construction() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("shot.png");
+        std::fs::write(&path, b"png").unwrap();
+
+        let manual = vec![
+            InputItem::Text {
+                text: "compare these".to_string(),
+            },
+            InputItem::Image {
+                image_url: "https://example.com/a.png".to_string(),
+                detail: None,
+            },
+            InputItem::LocalImage {
+                path: path.clone(),
+                detail: None,
+            },
+        ];
+        let builder = MessageBuilder::new()
+            .text("compare these")
+            .image_url("https://example.com/a.png")
+            .image_path(&path);
+
+        assert_eq!(builder.clone().into_items(), manual);
+        assert_eq!(builder.build(), ResponseInputItem::from(manual));
+    }
+
+    #[test]
+    fn function_call_output_accepts_bare_string() {
+        let item: ResponseItem = serde_json::from_value(serde_json::json!({
+            "type": "function_call_output",
+            "call_id": "call_1",
+            "output": "exit code 0",
+        }))
+        .unwrap();
+
+        let ResponseItem::FunctionCallOutput { call_id, output } = item else {
+            panic!("expected a function call output");
+        };
+        assert_eq!(call_id, "call_1");
+        assert_eq!(output.content, "exit code 0");
+        assert_eq!(output.success, None);
+    }
+
+    #[test]
+    fn function_call_output_accepts_object() {
+        let item: ResponseItem = serde_json::from_value(serde_json::json!({
+            "type": "function_call_output",
+            "call_id": "call_1",
+            "output": {"content": "permission denied", "success": false},
+        }))
+        .unwrap();
+
+        let ResponseItem::FunctionCallOutput { output, .. } = item else {
+            panic!("expected a function call output");
+        };
+        assert_eq!(output.content, "permission denied");
+        assert_eq!(output.success, Some(false));
+        assert_eq!(output.exit_code, None);
+        assert_eq!(output.duration_ms, None);
+    }
+
+    #[test]
+    fn exit_code_and_duration_are_read_but_never_sent() {
+        let output: FunctionCallOutputPayload = serde_json::from_value(serde_json::json!({
+            "content": "exit 2",
+            "success": false,
+            "exit_code": 2,
+            "duration_ms": 1500,
+        }))
+        .unwrap();
+        assert_eq!(output.exit_code, Some(2));
+        assert_eq!(output.duration_ms, Some(1500));
+
+        let without = FunctionCallOutputPayload {
+            exit_code: None,
+            duration_ms: None,
+            ..output.clone()
+        };
+        assert_eq!(
+            serde_json::to_string(&output).unwrap(),
+            serde_json::to_string(&without).unwrap()
+        );
+        assert_eq!(serde_json::to_string(&output).unwrap(), r#""exit 2""#);
+    }
+
+    #[test]
+    fn mcp_mixed_content_is_split_into_text_and_images() {
+        let result: CallToolResult = serde_json::from_value(serde_json::json!({
+            "content": [
+                {"type": "text", "text": "Here is the chart:"},
+                {"type": "image", "data": "iVBORw0KGgo=", "mimeType": "image/png"},
+                {"type": "resource_link", "name": "report", "uri": "file:///tmp/report.csv"},
+            ],
+            "isError": false,
+        }))
+        .unwrap();
+
+        let items = ResponseInputItem::McpToolCallOutput {
+            call_id: "call1".into(),
+            result: Ok(result),
+        }
+        .into_response_items();
+
+        let [
+            ResponseItem::FunctionCallOutput { call_id, output },
+            ResponseItem::Message { role, content, .. },
+        ] = items.as_slice()
+        else {
+            panic!("unexpected items: {items:?}");
+        };
+        assert_eq!(call_id, "call1");
+        assert_eq!(
+            output.content,
+            "Here is the chart:\n[image 1: image/png]\n[resource: report](file:///tmp/report.csv)"
+        );
+        assert_eq!(output.success, Some(true));
+        assert_eq!(role, "user");
+        assert!(matches!(
+            content.as_slice(),
+            [ContentItem::InputImage { image_url, .. }] if image_url == "data:image/png;base64,iVBORw0KGgo="
+        ));
+    }
+
+    #[test]
+    fn mcp_tool_error_flag_is_preserved() {
+        let result: CallToolResult = serde_json::from_value(serde_json::json!({
+            "content": [{"type": "text", "text": "no such file"}],
+            "isError": true,
+        }))
+        .unwrap();
+
+        let item = ResponseItem::from(ResponseInputItem::McpToolCallOutput {
+            call_id: "call1".into(),
+            result: Ok(result),
+        });
+
+        let ResponseItem::FunctionCallOutput { output, .. } = item else {
+            panic!("expected function call output");
+        };
+        assert_eq!(output.content, "no such file");
+        assert_eq!(output.success, Some(false));
+    }
+
     #[test]
     fn serializes_success_as_plain_string() {
         let item = ResponseInputItem::FunctionCallOutput {
@@ -234,6 +840,8 @@ mod tests {
             output: FunctionCallOutputPayload {
                 content: "ok".into(),
                 success: None,
+                exit_code: None,
+                duration_ms: None,
             },
         };
 
@@ -251,6 +859,8 @@ mod tests {
             output: FunctionCallOutputPayload {
                 content: "bad".into(),
                 success: Some(false),
+                exit_code: None,
+                duration_ms: None,
             },
         };
 
@@ -273,9 +883,58 @@ mod tests {
             ShellToolCallParams {
                 command: vec!["ls".to_string(), "-l".to_string()],
                 workdir: Some("/tmp".to_string()),
+                env: None,
                 timeout_ms: Some(1000),
             },
             params
         );
     }
+
+    #[test]
+    fn deserialize_shell_tool_call_params_with_env() {
+        let params: ShellToolCallParams = serde_json::from_str(
+            r#"{"command": ["make"], "env": {"CC": "clang", "VERBOSE": "1"}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            params.env,
+            Some(HashMap::from([
+                ("CC".to_string(), "clang".to_string()),
+                ("VERBOSE".to_string(), "1".to_string()),
+            ]))
+        );
+    }
+
+    #[test]
+    fn deserialize_shell_tool_call_params_from_string_command() {
+        let from_string: ShellToolCallParams =
+            serde_json::from_str(r#"{"command": "ls -l /tmp", "timeout": 1000}"#).unwrap();
+        let from_array: ShellToolCallParams =
+            serde_json::from_str(r#"{"command": ["bash", "-lc", "ls -l /tmp"], "timeout": 1000}"#)
+                .unwrap();
+        assert_eq!(from_string, from_array);
+        assert_eq!(from_string.command, vec!["bash", "-lc", "ls -l /tmp"]);
+    }
+
+    fn shell_params(command: &[&str]) -> ShellToolCallParams {
+        ShellToolCallParams {
+            command: command.iter().map(|arg| arg.to_string()).collect(),
+            workdir: None,
+            env: None,
+            timeout_ms: None,
+        }
+    }
+
+    #[test]
+    fn validate_shell_tool_call_params() {
+        assert_eq!(shell_params(&["ls", "-l"]).validate(), Ok(()));
+        assert_eq!(
+            shell_params(&[]).validate(),
+            Err(ShellParamError::EmptyCommand)
+        );
+        assert_eq!(
+            shell_params(&["echo", "a\0b"]).validate(),
+            Err(ShellParamError::NulByte { index: 1 })
+        );
+    }
 }
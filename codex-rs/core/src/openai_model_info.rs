@@ -1,3 +1,5 @@
+use crate::protocol::TokenUsage;
+
 /// Metadata about a model, particularly OpenAI models.
 /// We may want to consider including details like the pricing for
 /// input tokens, output tokens, etc., though users will need to be able to
@@ -69,3 +71,36 @@ pub(crate) fn get_model_info(name: &str) -> Option<ModelInfo> {
         _ => None,
     }
 }
+
+/// Tokens still available in `model`'s context window after the cumulative
+/// `usage` of the session so far, or `None` if the window size of `model` is
+/// unknown. Saturates at zero once the window is exhausted.
+pub fn remaining_context(usage: &TokenUsage, model: &str) -> Option<u64> {
+    let info = get_model_info(model)?;
+    Some(info.context_window.saturating_sub(usage.total_tokens))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn usage(total_tokens: u64) -> TokenUsage {
+        TokenUsage {
+            total_tokens,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn remaining_context_for_known_model() {
+        assert_eq!(remaining_context(&usage(1_000), "o3"), Some(199_000));
+        assert_eq!(remaining_context(&usage(199_990), "o3"), Some(10));
+        assert_eq!(remaining_context(&usage(250_000), "o3"), Some(0));
+    }
+
+    #[test]
+    fn remaining_context_for_unknown_model_is_none() {
+        assert_eq!(remaining_context(&usage(1_000), "my-local-model"), None);
+    }
+}
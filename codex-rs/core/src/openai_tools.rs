@@ -1,99 +1,148 @@
 use serde::Serialize;
 use serde_json::json;
 use std::collections::BTreeMap;
+use std::collections::HashSet;
 use std::sync::LazyLock;
+use tracing::warn;
 
 use crate::client_common::Prompt;
 
-#[derive(Debug, Clone, Serialize)]
-pub(crate) struct ResponsesApiTool {
-    name: &'static str,
-    description: &'static str,
-    strict: bool,
-    parameters: JsonSchema,
+/// A tool offered to the model. When serialized as JSON, this produces a
+/// valid "Tool" in the OpenAI Responses API.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Tool {
+    /// A function the model calls by name with JSON arguments.
+    Function {
+        name: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        description: Option<String>,
+        /// Whether arguments must match `parameters` exactly. `None` leaves
+        /// it to the API default.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        strict: Option<bool>,
+        /// JSON Schema of the arguments.
+        parameters: serde_json::Value,
+    },
+    /// The hosted web search tool.
+    #[serde(rename = "web_search_preview")]
+    WebSearch,
+    /// Shell access through `local_shell_call` items. Codex models only.
+    LocalShell,
 }
 
-/// When serialized as JSON, this produces a valid "Tool" in the OpenAI
-/// Responses API.
-#[derive(Debug, Clone, Serialize)]
-#[serde(tag = "type")]
-pub(crate) enum OpenAiTool {
-    #[serde(rename = "function")]
-    Function(ResponsesApiTool),
-    #[serde(rename = "local_shell")]
-    LocalShell {},
-}
+impl Tool {
+    /// The `type` this tool is sent with.
+    fn kind(&self) -> &'static str {
+        match self {
+            Tool::Function { .. } => "function",
+            Tool::WebSearch => "web_search_preview",
+            Tool::LocalShell => "local_shell",
+        }
+    }
 
-/// Generic JSON‑Schema subset needed for our tool definitions
-#[derive(Debug, Clone, Serialize)]
-#[serde(tag = "type", rename_all = "lowercase")]
-pub(crate) enum JsonSchema {
-    String,
-    Number,
-    Array {
-        items: Box<JsonSchema>,
-    },
-    Object {
-        properties: BTreeMap<String, JsonSchema>,
-        required: &'static [&'static str],
-        #[serde(rename = "additionalProperties")]
-        additional_properties: bool,
-    },
+    /// Function tools are identified by name; built-in tools only by type.
+    fn display_name(&self) -> &str {
+        match self {
+            Tool::Function { name, .. } => name,
+            tool => tool.kind(),
+        }
+    }
 }
 
 /// Tool usage specification
-static DEFAULT_TOOLS: LazyLock<Vec<OpenAiTool>> = LazyLock::new(|| {
-    let mut properties = BTreeMap::new();
-    properties.insert(
-        "command".to_string(),
-        JsonSchema::Array {
-            items: Box::new(JsonSchema::String),
-        },
-    );
-    properties.insert("workdir".to_string(), JsonSchema::String);
-    properties.insert("timeout".to_string(), JsonSchema::Number);
-
-    vec![OpenAiTool::Function(ResponsesApiTool {
-        name: "shell",
-        description: "Runs a shell command, and returns its output.",
-        strict: false,
-        parameters: JsonSchema::Object {
-            properties,
-            required: &["command"],
-            additional_properties: false,
-        },
-    })]
+static DEFAULT_TOOLS: LazyLock<Vec<Tool>> = LazyLock::new(|| {
+    vec![Tool::Function {
+        name: "shell".to_string(),
+        description: Some("Runs a shell command, and returns its output.".to_string()),
+        strict: Some(false),
+        parameters: json!({
+            "type": "object",
+            "properties": {
+                "command": { "type": "array", "items": { "type": "string" } },
+                "workdir": { "type": "string" },
+                "timeout": { "type": "number" },
+            },
+            "required": ["command"],
+            "additionalProperties": false,
+        }),
+    }]
 });
 
-static DEFAULT_CODEX_MODEL_TOOLS: LazyLock<Vec<OpenAiTool>> =
-    LazyLock::new(|| vec![OpenAiTool::LocalShell {}]);
+static DEFAULT_CODEX_MODEL_TOOLS: LazyLock<Vec<Tool>> = LazyLock::new(|| vec![Tool::LocalShell]);
 
-/// Returns JSON values that are compatible with Function Calling in the
-/// Responses API:
+/// Returns the tools to offer `model` through the Responses API: the
+/// built-in tools, then `prompt.tools`, then the MCP tools in
+/// `prompt.extra_tools` sorted by name, minus any the model does not support.
 /// https://platform.openai.com/docs/guides/function-calling?api-mode=responses
-pub(crate) fn create_tools_json_for_responses_api(
+pub(crate) fn create_tools_for_responses_api(
     prompt: &Prompt,
     model: &str,
-) -> crate::error::Result<Vec<serde_json::Value>> {
-    // Assemble tool list: built-in tools + any extra tools from the prompt.
+) -> crate::error::Result<Vec<Tool>> {
     let default_tools = if model.starts_with("codex") {
         &DEFAULT_CODEX_MODEL_TOOLS
     } else {
         &DEFAULT_TOOLS
     };
-    let mut tools_json = Vec::with_capacity(default_tools.len() + prompt.extra_tools.len());
-    for t in default_tools.iter() {
-        tools_json.push(serde_json::to_value(t)?);
-    }
-    tools_json.extend(
-        prompt
-            .extra_tools
-            .clone()
-            .into_iter()
-            .map(|(name, tool)| mcp_tool_to_openai_tool(name, tool)),
-    );
+    let mut tools =
+        Vec::with_capacity(default_tools.len() + prompt.tools.len() + prompt.extra_tools.len());
+    tools.extend(default_tools.iter().cloned());
+    tools.extend(prompt.tools.iter().cloned());
+    // Sorted so that identical prompts produce byte-identical requests, which
+    // keeps request snapshots stable and helps prompt caching.
+    let extra_tools: BTreeMap<_, _> = prompt.extra_tools.clone().into_iter().collect();
+    for (name, tool) in extra_tools {
+        tools.push(mcp_tool_to_openai_tool(name, tool)?);
+    }
 
-    Ok(tools_json)
+    let (kept, dropped) = filter_supported_tools(drop_duplicate_names(tools), model);
+    if !dropped.is_empty() {
+        let names: Vec<&str> = dropped.iter().map(Tool::display_name).collect();
+        warn!("model {model} does not support these tools, omitting them: {names:?}");
+    }
+
+    Ok(kept)
+}
+
+/// Keeps the first tool of each name, since a request that offers two tools
+/// with the same name is rejected. Built-in tools come first, so neither
+/// `Prompt::tools` nor MCP tools can shadow them.
+fn drop_duplicate_names(tools: Vec<Tool>) -> Vec<Tool> {
+    let mut names = HashSet::new();
+    tools
+        .into_iter()
+        .filter(|tool| {
+            let unique = names.insert(tool.display_name().to_string());
+            if !unique {
+                warn!(
+                    "omitting tool {} because another tool has the same name",
+                    tool.display_name()
+                );
+            }
+            unique
+        })
+        .collect()
+}
+
+/// Tool `type`s accepted by `model`. Sending any other type makes the request
+/// fail outright, so such tools are dropped instead.
+fn supported_tool_types(model: &str) -> &'static [&'static str] {
+    if model.starts_with("codex") {
+        &["function", "local_shell"]
+    } else if model.starts_with("o1-mini") || model.starts_with("o1-preview") {
+        // These early reasoning models reject tools altogether.
+        &[]
+    } else {
+        &["function", "web_search_preview"]
+    }
+}
+
+/// Splits `tools` into those `model` can use and those it cannot.
+pub(crate) fn filter_supported_tools(tools: Vec<Tool>, model: &str) -> (Vec<Tool>, Vec<Tool>) {
+    let supported = supported_tool_types(model);
+    tools
+        .into_iter()
+        .partition(|tool| supported.contains(&tool.kind()))
 }
 
 /// Returns JSON values that are compatible with Function Calling in the
@@ -103,35 +152,29 @@ pub(crate) fn create_tools_json_for_chat_completions_api(
     prompt: &Prompt,
     model: &str,
 ) -> crate::error::Result<Vec<serde_json::Value>> {
-    // We start with the JSON for the Responses API and than rewrite it to match
-    // the chat completions tool call format.
-    let responses_api_tools_json = create_tools_json_for_responses_api(prompt, model)?;
-    let tools_json = responses_api_tools_json
-        .into_iter()
-        .filter_map(|mut tool| {
-            if tool.get("type") != Some(&serde_json::Value::String("function".to_string())) {
-                return None;
-            }
-
-            if let Some(map) = tool.as_object_mut() {
-                // Remove "type" field as it is not needed in chat completions.
-                map.remove("type");
-                Some(json!({
-                    "type": "function",
-                    "function": map,
-                }))
-            } else {
-                None
-            }
-        })
-        .collect::<Vec<serde_json::Value>>();
+    // We start with the tools for the Responses API and then rewrite them to
+    // match the chat completions tool call format, which only has functions.
+    let mut tools_json = Vec::new();
+    for tool in create_tools_for_responses_api(prompt, model)? {
+        if !matches!(tool, Tool::Function { .. }) {
+            continue;
+        }
+        if let serde_json::Value::Object(mut map) = serde_json::to_value(&tool)? {
+            // Remove "type" field as it is not needed in chat completions.
+            map.remove("type");
+            tools_json.push(json!({
+                "type": "function",
+                "function": map,
+            }));
+        }
+    }
     Ok(tools_json)
 }
 
 fn mcp_tool_to_openai_tool(
     fully_qualified_name: String,
     tool: mcp_types::Tool,
-) -> serde_json::Value {
+) -> serde_json::Result<Tool> {
     let mcp_types::Tool {
         description,
         mut input_schema,
@@ -146,12 +189,167 @@ fn mcp_tool_to_openai_tool(
         input_schema.properties = Some(serde_json::Value::Object(serde_json::Map::new()));
     }
 
-    // TODO(mbolin): Change the contract of this function to return
-    // ResponsesApiTool.
-    json!({
-        "name": fully_qualified_name,
-        "description": description,
-        "parameters": input_schema,
-        "type": "function",
+    Ok(Tool::Function {
+        name: fully_qualified_name,
+        description,
+        strict: None,
+        parameters: serde_json::to_value(input_schema)?,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::collections::HashMap;
+
+    fn tool_names(tools: &[Tool]) -> Vec<&str> {
+        tools.iter().map(Tool::display_name).collect()
+    }
+
+    fn function(name: &str) -> Tool {
+        Tool::Function {
+            name: name.to_string(),
+            description: None,
+            strict: None,
+            parameters: json!({ "type": "object", "properties": {} }),
+        }
+    }
+
+    #[test]
+    fn model_without_tool_support_drops_shell() {
+        let tools = create_tools_for_responses_api(&Prompt::default(), "o1-mini").unwrap();
+        assert_eq!(tool_names(&tools), Vec::<&str>::new());
+
+        let (kept, dropped) = filter_supported_tools(DEFAULT_TOOLS.clone(), "o1-mini");
+        assert!(kept.is_empty());
+        assert_eq!(tool_names(&dropped), vec!["shell"]);
+    }
+
+    fn mcp_tool(name: &str) -> mcp_types::Tool {
+        mcp_types::Tool {
+            annotations: None,
+            description: None,
+            input_schema: mcp_types::ToolInputSchema {
+                properties: None,
+                required: None,
+                r#type: "object".to_string(),
+            },
+            name: name.to_string(),
+            output_schema: None,
+            title: None,
+        }
+    }
+
+    fn prompt_with_mcp_tools<'a>(names: impl Iterator<Item = &'a String>) -> Prompt {
+        let extra_tools = names.map(|name| (name.clone(), mcp_tool(name))).collect();
+        Prompt {
+            extra_tools,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn mcp_tools_are_sent_sorted_regardless_of_insertion_order() {
+        let names: Vec<String> = (0..32).map(|i| format!("server__tool_{i:02}")).collect();
+        let forward =
+            create_tools_for_responses_api(&prompt_with_mcp_tools(names.iter()), "o3").unwrap();
+        let backward =
+            create_tools_for_responses_api(&prompt_with_mcp_tools(names.iter().rev()), "o3")
+                .unwrap();
+
+        assert_eq!(
+            serde_json::to_string(&forward).unwrap(),
+            serde_json::to_string(&backward).unwrap()
+        );
+        let mut expected = vec!["shell"];
+        expected.extend(names.iter().map(String::as_str));
+        assert_eq!(tool_names(&forward), expected);
+    }
+
+    #[test]
+    fn local_shell_is_kept_only_for_codex_models() {
+        let tools = vec![Tool::LocalShell, function("shell")];
+
+        let (kept, dropped) = filter_supported_tools(tools.clone(), "codex-mini-latest");
+        assert_eq!(tool_names(&kept), vec!["local_shell", "shell"]);
+        assert!(dropped.is_empty());
+
+        let (kept, dropped) = filter_supported_tools(tools, "gpt-4.1");
+        assert_eq!(tool_names(&kept), vec!["shell"]);
+        assert_eq!(tool_names(&dropped), vec!["local_shell"]);
+    }
+
+    #[test]
+    fn tools_with_taken_names_are_dropped() {
+        let prompt = Prompt {
+            tools: vec![function("shell"), function("lookup")],
+            extra_tools: HashMap::from([("lookup".to_string(), mcp_tool("lookup"))]),
+            ..Default::default()
+        };
+
+        let tools = create_tools_for_responses_api(&prompt, "o3").unwrap();
+        assert_eq!(tool_names(&tools), vec!["shell", "lookup"]);
+        assert_eq!(tools[0], DEFAULT_TOOLS[0]);
+        assert_eq!(tools[1], function("lookup"));
+    }
+
+    #[test]
+    fn tools_serialize_to_responses_api_shapes() {
+        let function = Tool::Function {
+            name: "lookup".to_string(),
+            description: Some("Looks up a symbol.".to_string()),
+            strict: Some(true),
+            parameters: json!({ "type": "object", "properties": {} }),
+        };
+        assert_eq!(
+            serde_json::to_value(&function).unwrap(),
+            json!({
+                "type": "function",
+                "name": "lookup",
+                "description": "Looks up a symbol.",
+                "strict": true,
+                "parameters": { "type": "object", "properties": {} },
+            })
+        );
+        assert_eq!(
+            serde_json::to_value(Tool::WebSearch).unwrap(),
+            json!({ "type": "web_search_preview" })
+        );
+        assert_eq!(
+            serde_json::to_value(Tool::LocalShell).unwrap(),
+            json!({ "type": "local_shell" })
+        );
+    }
+
+    #[test]
+    fn prompt_tools_are_merged_between_builtin_and_mcp_tools() {
+        let prompt = Prompt {
+            tools: vec![function("lookup"), Tool::WebSearch],
+            extra_tools: HashMap::from([("server__fetch".to_string(), mcp_tool("fetch"))]),
+            ..Default::default()
+        };
+
+        let tools = create_tools_for_responses_api(&prompt, "gpt-4.1").unwrap();
+        assert_eq!(
+            tool_names(&tools),
+            vec!["shell", "lookup", "web_search_preview", "server__fetch"]
+        );
+        assert_eq!(
+            serde_json::to_value(&tools[3]).unwrap(),
+            json!({
+                "type": "function",
+                "name": "server__fetch",
+                "parameters": { "type": "object", "properties": {} },
+            })
+        );
+
+        let chat_tools = create_tools_json_for_chat_completions_api(&prompt, "gpt-4.1").unwrap();
+        let chat_names: Vec<&str> = chat_tools
+            .iter()
+            .map(|tool| tool["function"]["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(chat_names, vec!["shell", "lookup", "server__fetch"]);
+    }
+}
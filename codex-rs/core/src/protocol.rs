@@ -248,15 +248,30 @@ pub enum InputItem {
     /// Pre‑encoded data: URI image.
     Image {
         image_url: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        detail: Option<ImageDetail>,
     },
 
     /// Local image path provided by the user.  This will be converted to an
     /// `Image` variant (base64 data URL) during request serialization.
     LocalImage {
         path: std::path::PathBuf,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        detail: Option<ImageDetail>,
     },
 }
 
+/// How closely the model should look at an image, which trades vision token
+/// cost against fidelity. When unset the model picks, as with `Auto`.
+/// See https://platform.openai.com/docs/guides/images-vision#specify-image-input-detail-level
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageDetail {
+    Low,
+    High,
+    Auto,
+}
+
 /// Event Queue Entry - events from agent
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Event {
@@ -336,7 +351,7 @@ pub struct TaskCompleteEvent {
     pub last_agent_message: Option<String>,
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize, Default)]
+#[derive(Debug, Clone, Deserialize, Serialize, Default, PartialEq, Eq)]
 pub struct TokenUsage {
     pub input_tokens: u64,
     pub cached_input_tokens: Option<u64>,
@@ -345,6 +360,44 @@ pub struct TokenUsage {
     pub total_tokens: u64,
 }
 
+impl TokenUsage {
+    /// Starting point for accumulating usage across turns with `+=`.
+    pub fn zero() -> Self {
+        Self::default()
+    }
+}
+
+/// Sums two usages field by field. A missing optional count is treated as
+/// zero, and the result is `None` only if it is missing on both sides.
+impl std::ops::Add for TokenUsage {
+    type Output = TokenUsage;
+
+    fn add(self, rhs: TokenUsage) -> TokenUsage {
+        fn add_optional(a: Option<u64>, b: Option<u64>) -> Option<u64> {
+            match (a, b) {
+                (None, None) => None,
+                (a, b) => Some(a.unwrap_or(0) + b.unwrap_or(0)),
+            }
+        }
+        TokenUsage {
+            input_tokens: self.input_tokens + rhs.input_tokens,
+            cached_input_tokens: add_optional(self.cached_input_tokens, rhs.cached_input_tokens),
+            output_tokens: self.output_tokens + rhs.output_tokens,
+            reasoning_output_tokens: add_optional(
+                self.reasoning_output_tokens,
+                rhs.reasoning_output_tokens,
+            ),
+            total_tokens: self.total_tokens + rhs.total_tokens,
+        }
+    }
+}
+
+impl std::ops::AddAssign for TokenUsage {
+    fn add_assign(&mut self, rhs: TokenUsage) {
+        *self = std::mem::take(self) + rhs;
+    }
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct AgentMessageEvent {
     pub message: String,
@@ -362,6 +415,10 @@ pub struct AgentReasoningEvent {
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct AgentReasoningDeltaEvent {
+    /// Id of the reasoning item the delta belongs to; deltas sharing an id
+    /// make up that item's summary. Empty if the provider did not send one.
+    #[serde(default)]
+    pub item_id: String,
     pub delta: String,
 }
 
@@ -537,6 +594,46 @@ mod tests {
     #![allow(clippy::unwrap_used)]
     use super::*;
 
+    fn usage(cached: Option<u64>, reasoning: Option<u64>) -> TokenUsage {
+        TokenUsage {
+            input_tokens: 100,
+            cached_input_tokens: cached,
+            output_tokens: 20,
+            reasoning_output_tokens: reasoning,
+            total_tokens: 120,
+        }
+    }
+
+    #[test]
+    fn token_usage_adds_up() {
+        let mut total = TokenUsage::zero();
+        total += usage(None, None);
+        assert_eq!(total, usage(None, None));
+
+        total += usage(Some(40), None);
+        assert_eq!(
+            total,
+            TokenUsage {
+                input_tokens: 200,
+                cached_input_tokens: Some(40),
+                output_tokens: 40,
+                reasoning_output_tokens: None,
+                total_tokens: 240,
+            }
+        );
+
+        assert_eq!(
+            usage(Some(1), Some(2)) + usage(Some(3), Some(4)),
+            TokenUsage {
+                input_tokens: 200,
+                cached_input_tokens: Some(4),
+                output_tokens: 40,
+                reasoning_output_tokens: Some(6),
+                total_tokens: 240,
+            }
+        );
+    }
+
     /// Serialize Event to verify that its JSON representation has the expected
     /// amount of nesting.
     #[test]
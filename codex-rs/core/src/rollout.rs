@@ -1,32 +1,106 @@
-//! Persist Codex session rollouts (.jsonl) so sessions can be replayed or inspected later.
+//! Persist Codex session rollouts (.jsonl, optionally gzip-compressed as
+//! .jsonl.gz) so sessions can be replayed or inspected later.
 
+use std::collections::HashMap;
+use std::collections::VecDeque;
 use std::fs::File;
 use std::fs::{self};
+use std::future::Future;
 use std::io::Error as IoError;
+use std::io::Read;
+use std::io::Write;
 use std::path::Path;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::PoisonError;
 
+use flate2::Compression;
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
 use serde::Deserialize;
 use serde::Serialize;
 use serde_json::Value;
+use sha2::Digest;
+use sha2::Sha256;
 use time::OffsetDateTime;
+use time::error::IndeterminateOffset;
 use time::format_description::FormatItem;
 use time::macros::format_description;
 use tokio::io::AsyncWriteExt;
 use tokio::sync::mpsc::Sender;
 use tokio::sync::mpsc::{self};
+use tokio::task::JoinHandle;
+use tracing::Instrument;
 use tracing::info;
+use tracing::warn;
 use uuid::Uuid;
 
 use crate::config::Config;
+use crate::conversation_tracing::TraceContext;
+use crate::conversation_tracing::create_rollout_write_span;
+use crate::conversation_tracing::record_rollout_queue_depth;
+use crate::conversation_tracing::record_rollout_write_bytes;
+use crate::error::RolloutReadError;
+use crate::git_info::GitInfo;
+use crate::git_info::collect_git_info;
+use crate::models::FunctionCallOutputPayload;
 use crate::models::ResponseItem;
 
 const SESSIONS_SUBDIR: &str = "sessions";
 
-#[derive(Serialize, Deserialize, Clone, Default)]
+/// Maximum length, in characters, of [`SessionSummary::instructions_preview`].
+const INSTRUCTIONS_PREVIEW_CHARS: usize = 80;
+
+/// Appended to the rollout filename to form the path of its integrity sidecar.
+const INTEGRITY_SIDECAR_SUFFIX: &str = ".sha256";
+
+/// Inserted before the extension of every part of a rotated rollout after the
+/// first, followed by the two-digit part number: `rollout-…-part02.jsonl`.
+const PART_MARKER: &str = "-part";
+
+/// Function call outputs with more content than this are serialized straight
+/// into the rollout file by the writer instead of into a `String` first.
+const LARGE_ITEM_BYTES: usize = 1024 * 1024;
+
+/// `type` of the line written in place of a function call output identical
+/// to one written shortly before (`rollout_dedupe_outputs`).
+const OUTPUT_REF_TYPE: &str = "function_call_output_ref";
+
+/// How many distinct function call outputs are remembered for
+/// `rollout_dedupe_outputs`.
+const RECENT_OUTPUTS: usize = 32;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct SessionMeta {
     pub id: Uuid,
     pub timestamp: String,
     pub instructions: Option<String>,
+    /// Model the session was started with. Empty for rollouts recorded
+    /// before this was tracked.
+    #[serde(default)]
+    pub model: String,
+    /// Id of the model provider the session was started with.
+    #[serde(default)]
+    pub provider: String,
+    /// Working directory of the session.
+    #[serde(default)]
+    pub cwd: String,
+    /// Git state of `cwd` at session start; `None` outside a Git work tree.
+    #[serde(default)]
+    pub git: Option<GitInfo>,
+}
+
+/// A recorded session as listed by [`RolloutRecorder::list_sessions`], e.g. for a session
+/// picker.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionSummary {
+    pub id: Uuid,
+    pub timestamp: String,
+    /// First line of the session's instructions, shortened for display.
+    pub instructions_preview: Option<String>,
+    pub path: PathBuf,
 }
 
 #[derive(Serialize, Deserialize, Default, Clone)]
@@ -34,16 +108,78 @@ pub struct SessionStateSnapshot {
     pub previous_response_id: Option<String>,
 }
 
+/// Latency breakdown for one model turn, recorded once the response stream
+/// has completed. Stored as its own `turn_timing` line so it never shows up
+/// among the conversation items.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq)]
+pub struct TurnTiming {
+    /// Milliseconds from sending the request to the first streamed output.
+    /// `None` when the turn produced no output at all.
+    pub time_to_first_token_ms: Option<u64>,
+    /// Milliseconds from sending the request to `response.completed`.
+    pub total_ms: u64,
+    /// Number of input items sent with the request.
+    pub input_items: usize,
+    /// Input tokens reported by the provider, when it reports usage.
+    pub input_tokens: Option<u64>,
+}
+
+/// Totals for a whole session, written as the last line of its rollout when
+/// the session ends so usage can be billed without replaying every item.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq)]
+pub struct SessionSummaryFooter {
+    /// Prompt tokens across all turns.
+    pub input_tokens: u64,
+    /// Completion tokens across all turns.
+    pub output_tokens: u64,
+    pub total_tokens: u64,
+    /// Number of model turns in the session.
+    pub turns: u64,
+}
+
+/// `type` tag of the line holding a [`SessionSummaryFooter`].
+const SESSION_SUMMARY_TYPE: &str = "session_summary";
+
 #[derive(Serialize, Deserialize, Default, Clone)]
 pub struct SavedSession {
     pub session: SessionMeta,
     #[serde(default)]
     pub items: Vec<ResponseItem>,
+    /// Labels attached to each entry of `items`, index for index. Items that
+    /// were recorded without labels have an empty map.
+    #[serde(default)]
+    pub item_labels: Vec<HashMap<String, String>>,
     #[serde(default)]
     pub state: SessionStateSnapshot,
+    /// Per-turn latency records, in the order the turns completed.
+    #[serde(default)]
+    pub turn_timings: Vec<TurnTiming>,
+    /// Footer written when the session last ended, if any.
+    #[serde(default)]
+    pub summary: Option<SessionSummaryFooter>,
     pub session_id: Uuid,
 }
 
+/// On-disk envelope for an item recorded with
+/// [`RolloutRecorder::record_item_with_meta`].
+#[derive(Serialize, Deserialize)]
+struct LabeledItem<L, T> {
+    labels: L,
+    item: T,
+}
+
+/// Serializes `line`, wrapped in a [`LabeledItem`] envelope when `labels` is
+/// set.
+fn labeled_line<T: Serialize>(
+    line: T,
+    labels: Option<&HashMap<String, String>>,
+) -> serde_json::Result<String> {
+    match labels {
+        Some(labels) => serde_json::to_string(&LabeledItem { labels, item: line }),
+        None => serde_json::to_string(&line),
+    }
+}
+
 /// Records all [`ResponseItem`]s for a session and flushes them to disk after
 /// every update.
 ///
@@ -54,30 +190,83 @@ pub struct SavedSession {
 /// $ fx ~/.codex/sessions/rollout-2025-05-07T17-24-21-5973b6c0-94b8-487b-a530-2aeb6098ae0e.jsonl
 /// ```
 #[derive(Clone)]
-pub(crate) struct RolloutRecorder {
+pub struct RolloutRecorder {
     tx: Sender<RolloutCmd>,
+    /// Keep [`ResponseItem::Reasoning`] items instead of dropping them.
+    persist_reasoning: bool,
+    /// Digest function call outputs so that repeats can be written as
+    /// references (`rollout_dedupe_outputs`).
+    dedupe_outputs: bool,
+    /// The writer task, taken by [`RolloutRecorder::shutdown`]. Shared
+    /// between clones so whichever clone shuts down can join it.
+    writer: Arc<Mutex<Option<JoinHandle<()>>>>,
+    /// Session and turn for the `rollout_write` spans. The session keeps the
+    /// turn current; shared between clones like `writer`.
+    trace: Arc<Mutex<TraceContext>>,
 }
 
 #[derive(Clone)]
 enum RolloutCmd {
-    AddItems(Vec<ResponseItem>),
+    /// Items already serialized to JSON lines by
+    /// [`RolloutRecorder::record_items`].
+    AddItems(Vec<String>),
+    /// A single item too large to serialize up front; see
+    /// [`LARGE_ITEM_BYTES`].
+    AddLargeItem {
+        item: Box<ResponseItem>,
+        labels: Option<HashMap<String, String>>,
+    },
+    /// A function call output with the digest of its content, written as a
+    /// reference if the same content was written recently.
+    AddOutput {
+        json: String,
+        /// What to write instead of `json` if the output was seen recently.
+        ref_json: String,
+        digest: String,
+    },
     UpdateState(SessionStateSnapshot),
+    RecordTurnTiming(TurnTiming),
+    RecordSummary(SessionSummaryFooter),
+    /// Stop accepting commands once everything queued before this one has
+    /// been written.
+    Shutdown,
 }
 
 impl RolloutRecorder {
     /// Attempt to create a new [`RolloutRecorder`]. If the sessions directory
     /// cannot be created or the rollout file cannot be opened we return the
     /// error so the caller can decide whether to disable persistence.
+    ///
+    /// With a `sink`, lines go there instead of to a file under the sessions
+    /// directory. Rotation and integrity digests only apply to files, so
+    /// `rollout_max_bytes` and `rollout_integrity_hash` are ignored then.
     pub async fn new(
         config: &Config,
         uuid: Uuid,
         instructions: Option<String>,
+        sink: Option<Box<dyn RolloutSink>>,
     ) -> std::io::Result<Self> {
-        let LogFileInfo {
-            file,
-            session_id,
-            timestamp,
-        } = create_log_file(config, uuid)?;
+        Self::new_with_clock(config, uuid, instructions, sink, &SystemClock).await
+    }
+
+    /// [`RolloutRecorder::new`] with the session start time, used for both the
+    /// filename and [`SessionMeta::timestamp`], taken from `clock`.
+    pub async fn new_with_clock(
+        config: &Config,
+        uuid: Uuid,
+        instructions: Option<String>,
+        sink: Option<Box<dyn RolloutSink>>,
+        clock: &dyn Clock,
+    ) -> std::io::Result<Self> {
+        let timestamp = session_start_time(config.rollout_utc_timestamps, clock);
+        let (sink, path) = match sink {
+            Some(sink) => (WriterSink::Custom(sink), None),
+            None => {
+                let LogFileInfo { file, path } = create_log_file(config, uuid, timestamp)?;
+                let sink = FileRolloutSink::new(tokio::fs::File::from_std(file), is_gzip(&path));
+                (WriterSink::File(sink), Some(path))
+            }
+        };
 
         let timestamp_format: &[FormatItem] = format_description!(
             "[year]-[month]-[day]T[hour]:[minute]:[second].[subsecond digits:3]Z"
@@ -88,10 +277,44 @@ impl RolloutRecorder {
 
         let meta = SessionMeta {
             timestamp,
-            id: session_id,
+            id: uuid,
             instructions,
+            model: config.model.clone(),
+            provider: config.model_provider_id.clone(),
+            cwd: config.cwd.display().to_string(),
+            git: collect_git_info(&config.cwd).await,
         };
 
+        let rotation = path.as_ref().and_then(|path| {
+            config.rollout_max_bytes.map(|max_bytes| Rotation {
+                max_bytes,
+                first_path: path.clone(),
+                part: 1,
+                meta: meta.clone(),
+                part_has_entries: false,
+            })
+        });
+        let integrity_path = path.filter(|_| config.rollout_integrity_hash);
+        Ok(Self::spawn(
+            sink,
+            uuid,
+            Some(meta),
+            integrity_path,
+            rotation,
+            config.rollout_persist_reasoning,
+            config.rollout_dedupe_outputs,
+        ))
+    }
+
+    fn spawn(
+        sink: WriterSink,
+        session_id: Uuid,
+        meta: Option<SessionMeta>,
+        integrity_path: Option<PathBuf>,
+        rotation: Option<Rotation>,
+        persist_reasoning: bool,
+        dedupe_outputs: bool,
+    ) -> Self {
         // A reasonably-sized bounded channel. If the buffer fills up the send
         // future will yield, which is fine – we only need to ensure we do not
         // perform *blocking* I/O on the caller’s thread.
@@ -100,37 +323,154 @@ impl RolloutRecorder {
         // Spawn a Tokio task that owns the file handle and performs async
         // writes. Using `tokio::fs::File` keeps everything on the async I/O
         // driver instead of blocking the runtime.
-        tokio::task::spawn(rollout_writer(
-            tokio::fs::File::from_std(file),
+        let recent_outputs = dedupe_outputs.then(RecentOutputs::default);
+        let writer = tokio::task::spawn(rollout_writer(
+            sink,
             rx,
-            Some(meta),
+            meta,
+            integrity_path,
+            rotation,
+            recent_outputs,
         ));
 
-        Ok(Self { tx })
+        Self {
+            tx,
+            persist_reasoning,
+            dedupe_outputs,
+            writer: Arc::new(Mutex::new(Some(writer))),
+            trace: Arc::new(Mutex::new(TraceContext {
+                session_id,
+                turn_index: 0,
+            })),
+        }
+    }
+
+    /// Sets the turn that items recorded from now on belong to, for tracing.
+    pub(crate) fn set_turn_index(&self, turn_index: u64) {
+        self.trace
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .turn_index = turn_index;
+    }
+
+    fn trace_context(&self) -> TraceContext {
+        *self.trace.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Waits until everything recorded so far has been written and flushed,
+    /// then closes the rollout. Recording through any clone of this recorder
+    /// fails afterwards. Call this before exiting so the last turn is not
+    /// lost.
+    pub async fn shutdown(self) -> std::io::Result<()> {
+        let writer = self
+            .writer
+            .lock()
+            .map_err(|_| IoError::other("rollout writer lock poisoned"))?
+            .take();
+        let Some(writer) = writer else {
+            // Another clone already shut the recorder down.
+            return Ok(());
+        };
+        // If the writer is already gone the send fails, which is fine: there
+        // is nothing left to flush.
+        let _ = self.tx.send(RolloutCmd::Shutdown).await;
+        writer
+            .await
+            .map_err(|e| IoError::other(format!("rollout writer task failed: {e}")))
     }
 
     pub(crate) async fn record_items(&self, items: &[ResponseItem]) -> std::io::Result<()> {
-        let mut filtered = Vec::new();
-        for item in items {
-            match item {
-                // Note that function calls may look a bit strange if they are
-                // "fully qualified MCP tool calls," so we could consider
-                // reformatting them in that case.
-                ResponseItem::Message { .. }
-                | ResponseItem::LocalShellCall { .. }
-                | ResponseItem::FunctionCall { .. }
-                | ResponseItem::FunctionCallOutput { .. } => filtered.push(item.clone()),
-                ResponseItem::Reasoning { .. } | ResponseItem::Other => {
-                    // These should never be serialized.
-                    continue;
+        self.record(items, None).await
+    }
+
+    /// Like [`Self::record_items`] for a single item, but tags it with
+    /// free-form `labels` (e.g. to mark items produced by a retry or by a
+    /// sub-agent). The item is written as `{"labels": {...}, "item": {...}}`
+    /// and the labels are surfaced again in [`SavedSession::item_labels`] on
+    /// resume.
+    pub async fn record_item_with_meta(
+        &self,
+        item: &ResponseItem,
+        labels: HashMap<String, String>,
+    ) -> std::io::Result<()> {
+        self.record(std::slice::from_ref(item), Some(&labels)).await
+    }
+
+    /// Serializes and queues `items` inside one `rollout_write` span.
+    async fn record(
+        &self,
+        items: &[ResponseItem],
+        labels: Option<&HashMap<String, String>>,
+    ) -> std::io::Result<()> {
+        let span = create_rollout_write_span(&self.trace_context());
+        async {
+            let mut lines = Vec::new();
+            let mut bytes = 0;
+            for item in items
+                .iter()
+                .filter(|item| should_persist(item, self.persist_reasoning))
+            {
+                match large_item_bytes(item) {
+                    Some(size) => {
+                        // Keep the items in order: anything before the large
+                        // item is queued first.
+                        if !lines.is_empty() {
+                            self.queue(RolloutCmd::AddItems(std::mem::take(&mut lines)))
+                                .await?;
+                        }
+                        bytes += size;
+                        self.queue(RolloutCmd::AddLargeItem {
+                            item: Box::new(item.clone()),
+                            labels: labels.cloned(),
+                        })
+                        .await?;
+                    }
+                    None => match (self.dedupe_outputs, item) {
+                        (true, ResponseItem::FunctionCallOutput { call_id, output }) => {
+                            if !lines.is_empty() {
+                                self.queue(RolloutCmd::AddItems(std::mem::take(&mut lines)))
+                                    .await?;
+                            }
+                            let Ok(json) = labeled_line(RolloutItem(item), labels) else {
+                                continue;
+                            };
+                            let digest = output_digest(&output.content);
+                            let Ok(ref_json) = output_ref_line(call_id, &digest, output, labels)
+                            else {
+                                continue;
+                            };
+                            bytes += json.len();
+                            self.queue(RolloutCmd::AddOutput {
+                                json,
+                                ref_json,
+                                digest,
+                            })
+                            .await?;
+                        }
+                        _ => {
+                            if let Ok(line) = labeled_line(RolloutItem(item), labels) {
+                                bytes += line.len();
+                                lines.push(line);
+                            }
+                        }
+                    },
                 }
             }
+            record_rollout_write_bytes(bytes);
+            if lines.is_empty() {
+                return Ok(());
+            }
+            self.queue(RolloutCmd::AddItems(lines)).await
         }
-        if filtered.is_empty() {
-            return Ok(());
-        }
+        .instrument(span)
+        .await
+    }
+
+    /// Sends `cmd` to the writer. Called inside the `rollout_write` span.
+    async fn queue(&self, cmd: RolloutCmd) -> std::io::Result<()> {
+        record_rollout_queue_depth(self.tx.max_capacity() - self.tx.capacity());
         self.tx
-            .send(RolloutCmd::AddItems(filtered))
+            .send(cmd)
             .await
             .map_err(|e| IoError::other(format!("failed to queue rollout items: {e}")))
     }
@@ -142,63 +482,488 @@ impl RolloutRecorder {
             .map_err(|e| IoError::other(format!("failed to queue rollout state: {e}")))
     }
 
-    pub async fn resume(path: &Path) -> std::io::Result<(Self, SavedSession)> {
+    pub(crate) async fn record_turn_timing(&self, timing: TurnTiming) -> std::io::Result<()> {
+        self.tx
+            .send(RolloutCmd::RecordTurnTiming(timing))
+            .await
+            .map_err(|e| IoError::other(format!("failed to queue rollout turn timing: {e}")))
+    }
+
+    /// Appends the session's [`SessionSummaryFooter`]. Meant to be called
+    /// once, right before [`Self::shutdown`], so that it is the last line.
+    pub(crate) async fn record_summary(
+        &self,
+        summary: SessionSummaryFooter,
+    ) -> std::io::Result<()> {
+        self.tx
+            .send(RolloutCmd::RecordSummary(summary))
+            .await
+            .map_err(|e| IoError::other(format!("failed to queue rollout summary: {e}")))
+    }
+
+    /// Returns every recorded session, newest first, ordered like
+    /// [`latest_session`]. Files whose header cannot be parsed (e.g. empty or
+    /// corrupt rollouts) are skipped rather than failing the listing.
+    pub fn list_sessions(config: &Config) -> std::io::Result<Vec<SessionSummary>> {
+        list_sessions_in(&sessions_dir(config))
+    }
+
+    /// Loads a recorded session without reopening it for writing. The first
+    /// line must be the [`SessionMeta`] header; every following non-blank
+    /// line is an item. State snapshots and turn timings are skipped and
+    /// labeled items are unwrapped. The session summary footer, if present,
+    /// is returned separately. Malformed JSON is reported as a
+    /// [`RolloutReadError::BadItem`] with its line number rather than
+    /// silently dropped. `.gz` rollouts are decompressed
+    /// transparently, and a rollout rotated into several parts is read as a
+    /// whole, in order.
+    pub fn read_session(
+        path: &Path,
+    ) -> Result<(SessionMeta, Vec<ResponseItem>, Option<SessionSummaryFooter>), RolloutReadError>
+    {
+        let mut meta = None;
+        let mut items = Vec::new();
+        let mut summary = None;
+        let mut outputs = OutputContents::default();
+        for part in session_parts(path) {
+            let io_err = |source: IoError| match source.kind() {
+                std::io::ErrorKind::NotFound => RolloutReadError::NotFound { path: part.clone() },
+                _ => RolloutReadError::Io {
+                    path: part.clone(),
+                    source,
+                },
+            };
+            let text = decode_rollout(&part, fs::read(&part).map_err(io_err)?).map_err(io_err)?;
+            let mut lines = text.lines().enumerate();
+            // An empty file fails here as a header that ends before it starts.
+            let meta_line = lines.next().map_or("", |(_, line)| line);
+            // Every part repeats the header; the first one describes the session.
+            if meta.is_none() {
+                meta = Some(serde_json::from_str(meta_line).map_err(|source| {
+                    RolloutReadError::BadHeader {
+                        path: part.clone(),
+                        source,
+                    }
+                })?);
+            }
+
+            for (idx, line) in lines {
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let invalid = |source: serde_json::Error| RolloutReadError::BadItem {
+                    path: part.clone(),
+                    line: idx + 1,
+                    snippet: snippet(line),
+                    source,
+                };
+                let mut v: Value = serde_json::from_str(line).map_err(invalid)?;
+                if v.get("record_type").is_some() {
+                    continue;
+                }
+                if is_session_summary(&v) {
+                    summary = Some(serde_json::from_value(v).map_err(invalid)?);
+                    continue;
+                }
+                if v.get("type").is_none() {
+                    if let Some(item) = v.get_mut("item") {
+                        v = item.take();
+                    }
+                }
+                upgrade_function_call_output(&mut v);
+                if !outputs.resolve(&mut v) {
+                    return Err(RolloutReadError::UnknownOutputRef {
+                        path: part.clone(),
+                        line: idx + 1,
+                    });
+                }
+                let item: ResponseItem = serde_json::from_value(v).map_err(invalid)?;
+                if should_persist(&item, true) {
+                    items.push(item);
+                }
+            }
+        }
+        let meta = meta.ok_or_else(|| RolloutReadError::NotFound {
+            path: path.to_path_buf(),
+        })?;
+        Ok((meta, items, summary))
+    }
+
+    /// Reopens the rollout at `path` in append mode so a previous session can
+    /// be continued. The session id and header come from the existing file,
+    /// which is never rewritten: new items are appended after the old ones.
+    /// A compressed rollout is continued by appending a new gzip member, and a
+    /// rotated one by appending to its last part.
+    pub async fn resume(config: &Config, path: &Path) -> std::io::Result<(Self, SavedSession)> {
         info!("Resuming rollout from {path:?}");
-        let text = tokio::fs::read_to_string(path).await?;
-        let mut lines = text.lines();
-        let meta_line = lines
-            .next()
+        let parts = session_parts(path);
+        let mut texts = Vec::with_capacity(parts.len());
+        for part in &parts {
+            texts.push(decode_rollout(part, tokio::fs::read(part).await?)?);
+        }
+        let meta_line = texts
+            .first()
+            .and_then(|text| text.lines().next())
             .ok_or_else(|| IoError::other("empty session file"))?;
         let session: SessionMeta = serde_json::from_str(meta_line)
             .map_err(|e| IoError::other(format!("failed to parse session meta: {e}")))?;
+        // Skip the header that starts every part.
+        let lines = texts.iter().flat_map(|text| text.lines().skip(1));
         let mut items = Vec::new();
+        let mut item_labels = Vec::new();
         let mut state = SessionStateSnapshot::default();
+        let mut turn_timings = Vec::new();
+        let mut summary = None;
+        let mut outputs = OutputContents::default();
 
         for line in lines {
             if line.trim().is_empty() {
                 continue;
             }
-            let v: Value = match serde_json::from_str(line) {
+            let mut v: Value = match serde_json::from_str(line) {
                 Ok(v) => v,
                 Err(_) => continue,
             };
-            if v.get("record_type")
-                .and_then(|rt| rt.as_str())
-                .map(|s| s == "state")
-                .unwrap_or(false)
-            {
-                if let Ok(s) = serde_json::from_value::<SessionStateSnapshot>(v.clone()) {
-                    state = s
+            let mut labels = HashMap::new();
+            if v.get("type").is_none() && v.get("item").is_some() {
+                match serde_json::from_value::<LabeledItem<HashMap<String, String>, Value>>(v) {
+                    Ok(labeled) => {
+                        labels = labeled.labels;
+                        v = labeled.item;
+                    }
+                    Err(_) => continue,
                 }
+            }
+            upgrade_function_call_output(&mut v);
+            if !outputs.resolve(&mut v) {
                 continue;
             }
-            if let Ok(item) = serde_json::from_value::<ResponseItem>(v.clone()) {
-                match item {
-                    ResponseItem::Message { .. }
-                    | ResponseItem::LocalShellCall { .. }
-                    | ResponseItem::FunctionCall { .. }
-                    | ResponseItem::FunctionCallOutput { .. } => items.push(item),
-                    ResponseItem::Reasoning { .. } | ResponseItem::Other => {}
+            match v.get("record_type").and_then(|rt| rt.as_str()) {
+                Some("state") => {
+                    if let Ok(s) = serde_json::from_value::<SessionStateSnapshot>(v) {
+                        state = s
+                    }
+                    continue;
+                }
+                Some("turn_timing") => {
+                    if let Ok(t) = serde_json::from_value::<TurnTiming>(v) {
+                        turn_timings.push(t);
+                    }
+                    continue;
                 }
+                _ => {}
+            }
+            if is_session_summary(&v) {
+                summary = serde_json::from_value::<SessionSummaryFooter>(v).ok();
+                continue;
+            }
+            let Ok(item) = serde_json::from_value::<ResponseItem>(v) else {
+                continue;
+            };
+            // Reasoning items recorded with `rollout_persist_reasoning` are
+            // for inspection only and are not replayed into the history.
+            if should_persist(&item, false) {
+                items.push(item);
+                item_labels.push(labels);
             }
         }
 
         let saved = SavedSession {
             session: session.clone(),
-            items: items.clone(),
-            state: state.clone(),
+            items,
+            item_labels,
+            state,
+            turn_timings,
+            summary,
             session_id: session.id,
         };
 
+        // `session_parts` always returns at least the first part.
+        let first_path = parts[0].clone();
+        let path = parts[parts.len() - 1].as_path();
         let file = std::fs::OpenOptions::new()
             .append(true)
             .read(true)
             .open(path)?;
 
-        let (tx, rx) = mpsc::channel::<RolloutCmd>(256);
-        tokio::task::spawn(rollout_writer(tokio::fs::File::from_std(file), rx, None));
+        // Appending would invalidate an existing digest, so keep it current if
+        // the rollout was originally written with one.
+        let integrity_path = (config.rollout_integrity_hash
+            || integrity_sidecar_path(path).exists())
+        .then(|| path.to_path_buf());
+
+        let rotation = config.rollout_max_bytes.map(|max_bytes| Rotation {
+            max_bytes,
+            first_path,
+            part: u32::try_from(parts.len()).unwrap_or(u32::MAX),
+            meta: session.clone(),
+            part_has_entries: true,
+        });
+        let recorder = Self::spawn(
+            WriterSink::File(FileRolloutSink::new(
+                tokio::fs::File::from_std(file),
+                is_gzip(path),
+            )),
+            session.id,
+            None,
+            integrity_path,
+            rotation,
+            config.rollout_persist_reasoning,
+            config.rollout_dedupe_outputs,
+        );
         info!("Resumed rollout successfully from {path:?}");
-        Ok((Self { tx }, saved))
+        Ok((recorder, saved))
+    }
+}
+
+fn is_session_summary(v: &Value) -> bool {
+    v.get("type").and_then(Value::as_str) == Some(SESSION_SUMMARY_TYPE)
+}
+
+/// Hex SHA-256 of a function call output's content.
+fn output_digest(content: &str) -> String {
+    Sha256::digest(content.as_bytes())
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+/// Digests of the function call outputs the writer wrote most recently, for
+/// `rollout_dedupe_outputs`.
+#[derive(Default)]
+struct RecentOutputs {
+    digests: VecDeque<String>,
+}
+
+impl RecentOutputs {
+    /// Whether an output with `digest` was written recently. It counts as
+    /// written from now on either way.
+    fn seen(&mut self, digest: &str) -> bool {
+        if self.digests.iter().any(|d| d == digest) {
+            return true;
+        }
+        if self.digests.len() == RECENT_OUTPUTS {
+            self.digests.pop_front();
+        }
+        self.digests.push_back(digest.to_string());
+        false
+    }
+}
+
+/// Serializes a [`ResponseItem`] for the rollout. This is its wire form,
+/// except that a function call output is written as an object that keeps
+/// `success`, `exit_code` and `duration_ms`, which are not sent to the model.
+struct RolloutItem<'a>(&'a ResponseItem);
+
+impl Serialize for RolloutItem<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        #[derive(Serialize)]
+        #[serde(tag = "type", rename = "function_call_output")]
+        struct OutputLine<'a> {
+            call_id: &'a str,
+            output: OutputObject<'a>,
+        }
+
+        #[derive(Serialize)]
+        struct OutputObject<'a> {
+            content: &'a str,
+            #[serde(flatten)]
+            metadata: OutputMetadata,
+        }
+
+        match self.0 {
+            ResponseItem::FunctionCallOutput { call_id, output } => OutputLine {
+                call_id,
+                output: OutputObject {
+                    content: &output.content,
+                    metadata: OutputMetadata::of(output),
+                },
+            }
+            .serialize(serializer),
+            item => item.serialize(serializer),
+        }
+    }
+}
+
+/// The fields of a function call output besides its content.
+#[derive(Serialize)]
+struct OutputMetadata {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    success: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    exit_code: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    duration_ms: Option<u64>,
+}
+
+impl OutputMetadata {
+    fn of(output: &FunctionCallOutputPayload) -> Self {
+        Self {
+            success: output.success,
+            exit_code: output.exit_code,
+            duration_ms: output.duration_ms,
+        }
+    }
+}
+
+/// Line written instead of a function call output that repeats a recent
+/// one. Only the content is shared, so the other fields are kept.
+#[derive(Serialize)]
+struct OutputRefLine<'a> {
+    r#type: &'static str,
+    call_id: &'a str,
+    hash: &'a str,
+    #[serde(flatten)]
+    metadata: OutputMetadata,
+}
+
+fn output_ref_line(
+    call_id: &str,
+    digest: &str,
+    output: &FunctionCallOutputPayload,
+    labels: Option<&HashMap<String, String>>,
+) -> serde_json::Result<String> {
+    let line = OutputRefLine {
+        r#type: OUTPUT_REF_TYPE,
+        call_id,
+        hash: digest,
+        metadata: OutputMetadata::of(output),
+    };
+    labeled_line(line, labels)
+}
+
+/// Contents of the function call outputs read so far, by digest, so that
+/// the references written by `rollout_dedupe_outputs` can be resolved.
+#[derive(Default)]
+struct OutputContents(HashMap<String, String>);
+
+impl OutputContents {
+    /// Replaces a reference line `v` with the function call output it stands
+    /// for, and remembers the content of every other function call output.
+    /// Returns `false` for a reference to an output that was never read.
+    /// Must be called after [`upgrade_function_call_output`].
+    fn resolve(&mut self, v: &mut Value) -> bool {
+        match v.get("type").and_then(Value::as_str) {
+            Some("function_call_output") => {
+                if let Some(content) = v.pointer("/output/content").and_then(Value::as_str) {
+                    self.0.insert(output_digest(content), content.to_string());
+                }
+                true
+            }
+            Some(OUTPUT_REF_TYPE) => {
+                let Some(content) = v
+                    .get("hash")
+                    .and_then(Value::as_str)
+                    .and_then(|hash| self.0.get(hash))
+                else {
+                    return false;
+                };
+                let mut output = serde_json::Map::new();
+                output.insert("content".to_string(), content.clone().into());
+                for field in ["success", "exit_code", "duration_ms"] {
+                    if let Some(value) = v.get(field) {
+                        output.insert(field.to_string(), value.clone());
+                    }
+                }
+                *v = serde_json::json!({
+                    "type": "function_call_output",
+                    "call_id": v.get("call_id").cloned().unwrap_or_default(),
+                    "output": output,
+                });
+                true
+            }
+            _ => true,
+        }
+    }
+}
+
+/// `function_call_output` items are written with `output` as a bare string
+/// (the wire format), while `FunctionCallOutputPayload` deserializes from an
+/// object. Rewrite the string form so such lines load.
+fn upgrade_function_call_output(v: &mut Value) {
+    if v.get("type").and_then(Value::as_str) != Some("function_call_output") {
+        return;
+    }
+    if let Some(output) = v.get_mut("output") {
+        if let Value::String(content) = output {
+            *output = serde_json::json!({ "content": std::mem::take(content) });
+        }
+    }
+}
+
+/// Content size of `item` if it is a function call output above
+/// [`LARGE_ITEM_BYTES`].
+fn large_item_bytes(item: &ResponseItem) -> Option<usize> {
+    match item {
+        ResponseItem::FunctionCallOutput { output, .. }
+            if output.content.len() > LARGE_ITEM_BYTES =>
+        {
+            Some(output.content.len())
+        }
+        _ => None,
+    }
+}
+
+/// Writes `item` and a newline to `writer`, streaming the JSON rather than
+/// building it in memory first. See [`labeled_line`] for `labels`.
+fn write_item_line(
+    writer: &mut impl Write,
+    item: &ResponseItem,
+    labels: Option<&HashMap<String, String>>,
+) -> std::io::Result<()> {
+    match labels {
+        Some(labels) => serde_json::to_writer(
+            &mut *writer,
+            &LabeledItem {
+                labels,
+                item: RolloutItem(item),
+            },
+        ),
+        None => serde_json::to_writer(&mut *writer, &RolloutItem(item)),
+    }
+    .map_err(IoError::from)?;
+    writer.write_all(b"\n")
+}
+
+/// Returns `true` for the item types that belong in a rollout.
+fn should_persist(item: &ResponseItem, persist_reasoning: bool) -> bool {
+    match item {
+        // Note that function calls may look a bit strange if they are
+        // "fully qualified MCP tool calls," so we could consider
+        // reformatting them in that case.
+        ResponseItem::Message { .. }
+        | ResponseItem::LocalShellCall { .. }
+        | ResponseItem::FunctionCall { .. }
+        | ResponseItem::FunctionCallOutput { .. } => true,
+        ResponseItem::Reasoning { .. } => persist_reasoning,
+        // These should never be serialized.
+        ResponseItem::Other => false,
+    }
+}
+
+/// Source of the time a new session starts at. Tests and reproducible builds
+/// can pass a fixed clock to [`RolloutRecorder::new_with_clock`].
+pub trait Clock: Send + Sync {
+    fn now_utc(&self) -> OffsetDateTime;
+
+    /// Current local time; fails where the UTC offset cannot be determined.
+    fn now_local(&self) -> Result<OffsetDateTime, IndeterminateOffset>;
+}
+
+/// The system clock, used by [`RolloutRecorder::new`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now_utc(&self) -> OffsetDateTime {
+        OffsetDateTime::now_utc()
+    }
+
+    fn now_local(&self) -> Result<OffsetDateTime, IndeterminateOffset> {
+        OffsetDateTime::now_local()
     }
 }
 
@@ -206,19 +971,31 @@ struct LogFileInfo {
     /// Opened file handle to the rollout file.
     file: File,
 
-    /// Session ID (also embedded in filename).
-    session_id: Uuid,
+    /// Location of the rollout file.
+    path: PathBuf,
+}
 
-    /// Timestamp for the start of the session.
-    timestamp: OffsetDateTime,
+/// Start time of a new session, used for the rollout filename and header.
+/// This is UTC with `rollout_utc_timestamps`, otherwise local time. Local time
+/// is unavailable where the UTC offset cannot be determined (e.g. containers
+/// without timezone data), in which case UTC is used as well.
+fn session_start_time(use_utc: bool, clock: &dyn Clock) -> OffsetDateTime {
+    if use_utc {
+        return clock.now_utc();
+    }
+    clock.now_local().unwrap_or_else(|e| {
+        warn!("failed to get local time ({e}); using UTC for the rollout");
+        clock.now_utc()
+    })
 }
 
-fn create_log_file(config: &Config, session_id: Uuid) -> std::io::Result<LogFileInfo> {
+fn create_log_file(
+    config: &Config,
+    session_id: Uuid,
+    timestamp: OffsetDateTime,
+) -> std::io::Result<LogFileInfo> {
     // Resolve ~/.codex/sessions/YYYY/MM/DD and create it if missing.
-    let timestamp = OffsetDateTime::now_local()
-        .map_err(|e| IoError::other(format!("failed to get local time: {e}")))?;
-    let mut dir = config.codex_home.clone();
-    dir.push(SESSIONS_SUBDIR);
+    let mut dir = sessions_dir(config);
     dir.push(timestamp.year().to_string());
     dir.push(format!("{:02}", u8::from(timestamp.month())));
     dir.push(format!("{:02}", timestamp.day()));
@@ -232,7 +1009,12 @@ fn create_log_file(config: &Config, session_id: Uuid) -> std::io::Result<LogFile
         .format(format)
         .map_err(|e| IoError::other(format!("failed to format timestamp: {e}")))?;
 
-    let filename = format!("rollout-{date_str}-{session_id}.jsonl");
+    let extension = if config.rollout_gzip {
+        "jsonl.gz"
+    } else {
+        "jsonl"
+    };
+    let filename = format!("rollout-{date_str}-{session_id}.{extension}");
 
     let path = dir.join(filename);
     let file = std::fs::OpenOptions::new()
@@ -240,60 +1022,1582 @@ fn create_log_file(config: &Config, session_id: Uuid) -> std::io::Result<LogFile
         .create(true)
         .open(&path)?;
 
-    Ok(LogFileInfo {
-        file,
-        session_id,
-        timestamp,
+    Ok(LogFileInfo { file, path })
+}
+
+/// Root directory under which rollouts are stored, one subdirectory per day:
+/// `rollout_dir` if configured, otherwise `$CODEX_HOME/sessions`.
+fn sessions_dir(config: &Config) -> PathBuf {
+    match &config.rollout_dir {
+        Some(dir) => dir.clone(),
+        None => config.codex_home.join(SESSIONS_SUBDIR),
+    }
+}
+
+/// Returns the most recently started session, e.g. to implement a
+/// `--continue` flag. Sessions are ordered by the timestamp in their
+/// [`SessionMeta`] header, with the file modification time breaking ties.
+/// Files whose header cannot be parsed are skipped.
+pub fn latest_session(config: &Config) -> std::io::Result<Option<PathBuf>> {
+    latest_session_in(&sessions_dir(config))
+}
+
+fn latest_session_in(dir: &Path) -> std::io::Result<Option<PathBuf>> {
+    Ok(list_sessions_in(dir)?
+        .into_iter()
+        .next()
+        .map(|summary| summary.path))
+}
+
+fn list_sessions_in(dir: &Path) -> std::io::Result<Vec<SessionSummary>> {
+    let mut rollouts = Vec::new();
+    collect_rollout_files(dir, &mut rollouts)?;
+
+    let mut sessions: Vec<((String, std::time::SystemTime), SessionSummary)> = rollouts
+        .into_iter()
+        // A rotated session is listed once, by its first part.
+        .filter(|path| part_number(path) == 1)
+        .filter_map(|path| {
+            let meta = read_session_meta(&path)?;
+            let modified = fs::metadata(&path)
+                .and_then(|m| m.modified())
+                .unwrap_or(std::time::UNIX_EPOCH);
+            let summary = SessionSummary {
+                id: meta.id,
+                timestamp: meta.timestamp.clone(),
+                instructions_preview: meta.instructions.as_deref().map(preview),
+                path,
+            };
+            Some(((meta.timestamp, modified), summary))
+        })
+        .collect();
+    sessions.sort_by(|(a, _), (b, _)| b.cmp(a));
+    Ok(sessions.into_iter().map(|(_, summary)| summary).collect())
+}
+
+/// First line of `instructions`, shortened to [`INSTRUCTIONS_PREVIEW_CHARS`].
+fn preview(instructions: &str) -> String {
+    let first_line = instructions.lines().next().unwrap_or_default().trim();
+    if first_line.chars().count() > INSTRUCTIONS_PREVIEW_CHARS {
+        let mut truncated: String = first_line
+            .chars()
+            .take(INSTRUCTIONS_PREVIEW_CHARS - 1)
+            .collect();
+        truncated.push('…');
+        truncated
+    } else {
+        first_line.to_string()
+    }
+}
+
+/// Recursively collects `*.jsonl` and `*.jsonl.gz` files under `dir`. A
+/// missing directory simply means no sessions have been recorded yet.
+fn collect_rollout_files(dir: &Path, out: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    for entry in entries {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_rollout_files(&path, out)?;
+        } else if path.extension().is_some_and(|ext| ext == "jsonl")
+            || path
+                .file_name()
+                .is_some_and(|name| name.to_string_lossy().ends_with(".jsonl.gz"))
+        {
+            out.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Parses the [`SessionMeta`] header of the rollout at `path`, returning
+/// `None` if the file is unreadable, empty or malformed.
+fn read_session_meta(path: &Path) -> Option<SessionMeta> {
+    use std::io::BufRead;
+
+    let file = File::open(path).ok()?;
+    let reader: Box<dyn Read> = if is_gzip(path) {
+        Box::new(MultiGzDecoder::new(file))
+    } else {
+        Box::new(file)
+    };
+    let first_line = std::io::BufReader::new(reader).lines().next()?.ok()?;
+    serde_json::from_str(&first_line).ok()
+}
+
+fn is_gzip(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "gz")
+}
+
+/// Splits a rollout file name into the part before the part marker and the
+/// extension, plus the part number (`1` for the first part, which has no
+/// marker). Returns `None` for names without a rollout extension.
+fn split_part_name(name: &str) -> Option<(&str, u32, &str)> {
+    let ext = [".jsonl.gz", ".jsonl"]
+        .into_iter()
+        .find(|ext| name.ends_with(ext))?;
+    let stem = &name[..name.len() - ext.len()];
+    let part = stem
+        .rsplit_once(PART_MARKER)
+        .filter(|(_, digits)| digits.len() >= 2 && digits.bytes().all(|b| b.is_ascii_digit()))
+        .and_then(|(base, digits)| Some((base, digits.parse().ok()?)));
+    Some(match part {
+        Some((base, n)) => (base, n, ext),
+        None => (stem, 1, ext),
     })
 }
 
-async fn rollout_writer(
-    mut file: tokio::fs::File,
-    mut rx: mpsc::Receiver<RolloutCmd>,
-    meta: Option<SessionMeta>,
-) {
-    if let Some(meta) = meta {
-        if let Ok(json) = serde_json::to_string(&meta) {
-            let _ = file.write_all(json.as_bytes()).await;
-            let _ = file.write_all(b"\n").await;
-            let _ = file.flush().await;
+/// Part number of the rollout file at `path`; `1` unless it is a later part
+/// of a rotated rollout.
+fn part_number(path: &Path) -> u32 {
+    path.file_name()
+        .and_then(|name| split_part_name(&name.to_string_lossy()).map(|(_, n, _)| n))
+        .unwrap_or(1)
+}
+
+/// Path of part `part` of the rollout that `path` belongs to. A file not
+/// named like a rollout has no parts, so `path` itself is returned.
+fn part_path(path: &Path, part: u32) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let Some((base, _, ext)) = split_part_name(&name) else {
+        return path.to_path_buf();
+    };
+    let name = if part <= 1 {
+        format!("{base}{ext}")
+    } else {
+        format!("{base}{PART_MARKER}{part:02}{ext}")
+    };
+    path.with_file_name(name)
+}
+
+/// Longest prefix of a malformed line quoted in a [`RolloutReadError`].
+const SNIPPET_CHARS: usize = 80;
+
+/// The start of `line`, shortened to [`SNIPPET_CHARS`] characters.
+fn snippet(line: &str) -> String {
+    match line.char_indices().nth(SNIPPET_CHARS) {
+        Some((end, _)) => format!("{}…", &line[..end]),
+        None => line.to_string(),
+    }
+}
+
+/// Every part of the rollout that `path` belongs to, in order, starting with
+/// the first. A rollout that was never rotated has a single part, as does a
+/// file not named like a rollout.
+fn session_parts(path: &Path) -> Vec<PathBuf> {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    if split_part_name(&name).is_none() {
+        return vec![path.to_path_buf()];
+    }
+    let mut parts = vec![part_path(path, 1)];
+    loop {
+        let next = part_path(path, parts.len() as u32 + 1);
+        if !next.exists() {
+            return parts;
         }
+        parts.push(next);
     }
-    while let Some(cmd) = rx.recv().await {
-        match cmd {
-            RolloutCmd::AddItems(items) => {
-                for item in items {
-                    match item {
-                        ResponseItem::Message { .. }
-                        | ResponseItem::LocalShellCall { .. }
-                        | ResponseItem::FunctionCall { .. }
-                        | ResponseItem::FunctionCallOutput { .. } => {
-                            if let Ok(json) = serde_json::to_string(&item) {
-                                let _ = file.write_all(json.as_bytes()).await;
-                                let _ = file.write_all(b"\n").await;
-                            }
-                        }
-                        ResponseItem::Reasoning { .. } | ResponseItem::Other => {}
-                    }
-                }
-                let _ = file.flush().await;
-            }
-            RolloutCmd::UpdateState(state) => {
-                #[derive(Serialize)]
-                struct StateLine<'a> {
-                    record_type: &'static str,
-                    #[serde(flatten)]
-                    state: &'a SessionStateSnapshot,
-                }
-                if let Ok(json) = serde_json::to_string(&StateLine {
-                    record_type: "state",
-                    state: &state,
-                }) {
-                    let _ = file.write_all(json.as_bytes()).await;
-                    let _ = file.write_all(b"\n").await;
-                    let _ = file.flush().await;
-                }
+}
+
+/// Returns the contents of a rollout read from `path`, decompressing it if
+/// it is a `.gz` file. A compressed rollout whose session is still running
+/// (or crashed) has no gzip trailer yet; everything up to the last complete
+/// line is returned in that case.
+fn decode_rollout(path: &Path, bytes: Vec<u8>) -> std::io::Result<String> {
+    let bytes = if is_gzip(path) {
+        let mut decoded = Vec::new();
+        match MultiGzDecoder::new(bytes.as_slice()).read_to_end(&mut decoded) {
+            Ok(_) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+                let complete = decoded
+                    .iter()
+                    .rposition(|b| *b == b'\n')
+                    .map_or(0, |i| i + 1);
+                decoded.truncate(complete);
             }
+            Err(e) => return Err(e),
         }
+        decoded
+    } else {
+        bytes
+    };
+    String::from_utf8(bytes).map_err(|e| {
+        IoError::new(
+            std::io::ErrorKind::InvalidData,
+            format!("{}: rollout is not valid UTF-8: {e}", path.display()),
+        )
+    })
+}
+
+/// Returns the path of the `.sha256` sidecar for the rollout at `path`.
+fn integrity_sidecar_path(path: &Path) -> PathBuf {
+    let mut sidecar = path.as_os_str().to_owned();
+    sidecar.push(INTEGRITY_SIDECAR_SUFFIX);
+    PathBuf::from(sidecar)
+}
+
+/// Hex-encoded SHA-256 digest of the full contents of the rollout at `path`.
+async fn rollout_digest(path: &Path) -> std::io::Result<String> {
+    let contents = tokio::fs::read(path).await?;
+    Ok(Sha256::digest(&contents)
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect())
+}
+
+async fn write_integrity_sidecar(path: &Path) -> std::io::Result<()> {
+    let digest = rollout_digest(path).await?;
+    tokio::fs::write(integrity_sidecar_path(path), format!("{digest}\n")).await
+}
+
+/// Recomputes the digest of the rollout at `path` and compares it against the
+/// `.sha256` sidecar written when the session was closed. Returns `Ok(false)`
+/// if the rollout was modified after the digest was recorded, and an error if
+/// either file cannot be read.
+pub async fn verify_rollout_integrity(path: &Path) -> std::io::Result<bool> {
+    let expected = tokio::fs::read_to_string(integrity_sidecar_path(path)).await?;
+    let actual = rollout_digest(path).await?;
+    Ok(expected.trim() == actual)
+}
+
+/// Future returned by the methods of [`RolloutSink`].
+pub type SinkFuture<'a> = Pin<Box<dyn Future<Output = std::io::Result<()>> + Send + 'a>>;
+
+/// Destination of a rollout, e.g. object storage instead of local disk. The
+/// writer task owns the sink and calls it for one line at a time, in order,
+/// so implementations need no synchronization of their own.
+pub trait RolloutSink: Send {
+    /// Appends one JSON line; `line` does not include the newline.
+    fn write_line<'a>(&'a mut self, line: &'a str) -> SinkFuture<'a>;
+
+    /// Makes everything written so far durable. Called after every batch of
+    /// lines and once more when the rollout is complete.
+    fn flush(&mut self) -> SinkFuture<'_>;
+}
+
+/// The rollout file under the sessions directory. Compressed output is
+/// encoded into an in-memory buffer that is drained to the file on every
+/// flush, so the writer task never blocks the runtime on compression I/O.
+enum FileRolloutSink {
+    Plain(tokio::fs::File),
+    Gzip {
+        file: tokio::fs::File,
+        encoder: GzEncoder<Vec<u8>>,
+    },
+}
+
+impl RolloutSink for FileRolloutSink {
+    fn write_line<'a>(&'a mut self, json: &'a str) -> SinkFuture<'a> {
+        Box::pin(async move {
+            match self {
+                FileRolloutSink::Plain(file) => {
+                    file.write_all(json.as_bytes()).await?;
+                    file.write_all(b"\n").await
+                }
+                FileRolloutSink::Gzip { encoder, .. } => {
+                    encoder.write_all(json.as_bytes())?;
+                    encoder.write_all(b"\n")
+                }
+            }
+        })
+    }
+
+    fn flush(&mut self) -> SinkFuture<'_> {
+        Box::pin(async move {
+            match self {
+                FileRolloutSink::Plain(file) => file.flush().await,
+                FileRolloutSink::Gzip { file, encoder } => {
+                    // A sync flush makes everything written so far decodable
+                    // without waiting for the gzip trailer.
+                    encoder.flush()?;
+                    let compressed = std::mem::take(encoder.get_mut());
+                    file.write_all(&compressed).await?;
+                    file.flush().await
+                }
+            }
+        })
+    }
+}
+
+impl FileRolloutSink {
+    fn new(file: tokio::fs::File, gzip: bool) -> Self {
+        if gzip {
+            FileRolloutSink::Gzip {
+                file,
+                encoder: GzEncoder::new(Vec::new(), Compression::default()),
+            }
+        } else {
+            FileRolloutSink::Plain(file)
+        }
+    }
+
+    /// Writes `item` as one line without serializing it to a `String` first.
+    /// A plain file is written from a blocking task through a clone of the
+    /// handle; since the file is opened for appending, the line still lands
+    /// after everything written before it.
+    async fn write_large_item(
+        &mut self,
+        item: Box<ResponseItem>,
+        labels: Option<HashMap<String, String>>,
+    ) -> std::io::Result<()> {
+        match self {
+            FileRolloutSink::Plain(file) => {
+                file.flush().await?;
+                let file = file.try_clone().await?.into_std().await;
+                tokio::task::spawn_blocking(move || {
+                    let mut writer = std::io::BufWriter::new(file);
+                    write_item_line(&mut writer, &item, labels.as_ref())?;
+                    writer.flush()
+                })
+                .await
+                .map_err(|e| IoError::other(format!("rollout write task failed: {e}")))?
+            }
+            FileRolloutSink::Gzip { encoder, .. } => {
+                write_item_line(encoder, &item, labels.as_ref())
+            }
+        }
+    }
+
+    /// Size of the file on disk, i.e. after compression.
+    async fn len(&self) -> std::io::Result<u64> {
+        let file = match self {
+            FileRolloutSink::Plain(file) | FileRolloutSink::Gzip { file, .. } => file,
+        };
+        Ok(file.metadata().await?.len())
+    }
+
+    /// Flushes outstanding data and, for compressed output, writes the gzip
+    /// trailer.
+    async fn finish(self) -> std::io::Result<()> {
+        match self {
+            FileRolloutSink::Plain(mut file) => file.flush().await,
+            FileRolloutSink::Gzip { mut file, encoder } => {
+                let compressed = encoder.finish()?;
+                file.write_all(&compressed).await?;
+                file.flush().await
+            }
+        }
+    }
+}
+
+/// What the writer task writes to: the rollout file, or a sink passed to
+/// [`RolloutRecorder::new`].
+#[allow(clippy::large_enum_variant)]
+enum WriterSink {
+    File(FileRolloutSink),
+    Custom(Box<dyn RolloutSink>),
+}
+
+impl WriterSink {
+    async fn write_line(&mut self, json: &str) -> std::io::Result<()> {
+        match self {
+            WriterSink::File(sink) => sink.write_line(json).await,
+            WriterSink::Custom(sink) => sink.write_line(json).await,
+        }
+    }
+
+    async fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            WriterSink::File(sink) => sink.flush().await,
+            WriterSink::Custom(sink) => sink.flush().await,
+        }
+    }
+
+    /// See [`FileRolloutSink::write_large_item`]; a custom sink only accepts
+    /// whole lines, so the item is serialized for it as usual.
+    async fn write_large_item(
+        &mut self,
+        item: Box<ResponseItem>,
+        labels: Option<HashMap<String, String>>,
+    ) -> std::io::Result<()> {
+        match self {
+            WriterSink::File(sink) => sink.write_large_item(item, labels).await,
+            WriterSink::Custom(sink) => {
+                sink.write_line(&labeled_line(RolloutItem(&item), labels.as_ref())?)
+                    .await
+            }
+        }
+    }
+
+    async fn finish(self) -> std::io::Result<()> {
+        match self {
+            WriterSink::File(sink) => sink.finish().await,
+            WriterSink::Custom(mut sink) => sink.flush().await,
+        }
+    }
+}
+
+/// Size-based rotation (`rollout_max_bytes`): once the file being written
+/// exceeds `max_bytes`, the writer continues in a new `-partNN` file that
+/// starts with the same header.
+struct Rotation {
+    max_bytes: u64,
+    /// First part of the rollout; later parts are named after it.
+    first_path: PathBuf,
+    /// Number of the part being written, `1` for the first.
+    part: u32,
+    /// Header repeated at the top of every part.
+    meta: SessionMeta,
+    /// Whether anything besides the header went into the current part. A part
+    /// is never left with just its header, however small `max_bytes` is.
+    part_has_entries: bool,
+}
+
+impl Rotation {
+    /// Moves on to the next part if the current one is full. The new part is
+    /// opened before the full one is closed, so on failure writing simply
+    /// continues in the current part.
+    async fn rotate_if_full(
+        &mut self,
+        sink: &mut FileRolloutSink,
+        integrity_path: &mut Option<PathBuf>,
+    ) -> std::io::Result<()> {
+        if !self.part_has_entries || sink.len().await? <= self.max_bytes {
+            return Ok(());
+        }
+        let path = part_path(&self.first_path, self.part + 1);
+        if path == self.first_path {
+            // Not named like a rollout, so there is no name for another part.
+            return Ok(());
+        }
+        let file = tokio::fs::OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(&path)
+            .await?;
+        let mut next = FileRolloutSink::new(file, is_gzip(&path));
+        next.write_line(&serde_json::to_string(&self.meta)?).await?;
+        next.flush().await?;
+
+        let full = std::mem::replace(sink, next);
+        self.part += 1;
+        self.part_has_entries = false;
+        let full_path = integrity_path.as_mut().map(|p| std::mem::replace(p, path));
+
+        full.finish().await?;
+        // Each part gets its own digest, like a rollout that was never rotated.
+        if let Some(full_path) = full_path {
+            write_integrity_sidecar(&full_path).await?;
+        }
+        Ok(())
+    }
+}
+
+async fn rollout_writer(
+    mut sink: WriterSink,
+    mut rx: mpsc::Receiver<RolloutCmd>,
+    meta: Option<SessionMeta>,
+    mut integrity_path: Option<PathBuf>,
+    mut rotation: Option<Rotation>,
+    mut recent_outputs: Option<RecentOutputs>,
+) {
+    if let Some(meta) = meta {
+        if let Ok(json) = serde_json::to_string(&meta) {
+            let _ = sink.write_line(&json).await;
+            let _ = sink.flush().await;
+        }
+    }
+    while let Some(cmd) = rx.recv().await {
+        let is_shutdown = matches!(cmd, RolloutCmd::Shutdown);
+        if let (Some(rotation), WriterSink::File(file)) =
+            (rotation.as_mut().filter(|_| !is_shutdown), &mut sink)
+        {
+            if let Err(e) = rotation.rotate_if_full(file, &mut integrity_path).await {
+                warn!("failed to rotate rollout: {e}");
+            }
+            rotation.part_has_entries = true;
+        }
+        match cmd {
+            RolloutCmd::AddItems(lines) => {
+                // `record_items` has already filtered out what should not be
+                // persisted.
+                for line in lines {
+                    let _ = sink.write_line(&line).await;
+                }
+                let _ = sink.flush().await;
+            }
+            RolloutCmd::AddLargeItem { item, labels } => {
+                let repeated = match (recent_outputs.as_mut(), item.as_ref()) {
+                    (Some(recent), ResponseItem::FunctionCallOutput { call_id, output }) => {
+                        let digest = output_digest(&output.content);
+                        recent
+                            .seen(&digest)
+                            .then(|| {
+                                output_ref_line(call_id, &digest, output, labels.as_ref()).ok()
+                            })
+                            .flatten()
+                    }
+                    _ => None,
+                };
+                let written = match repeated {
+                    Some(json) => sink.write_line(&json).await,
+                    None => sink.write_large_item(item, labels).await,
+                };
+                if let Err(e) = written {
+                    warn!("failed to write rollout item: {e}");
+                }
+                let _ = sink.flush().await;
+            }
+            RolloutCmd::AddOutput {
+                json,
+                ref_json,
+                digest,
+            } => {
+                let repeated = recent_outputs
+                    .as_mut()
+                    .is_some_and(|recent| recent.seen(&digest));
+                let json = if repeated { ref_json } else { json };
+                let _ = sink.write_line(&json).await;
+                let _ = sink.flush().await;
+            }
+            RolloutCmd::UpdateState(state) => {
+                #[derive(Serialize)]
+                struct StateLine<'a> {
+                    record_type: &'static str,
+                    #[serde(flatten)]
+                    state: &'a SessionStateSnapshot,
+                }
+                if let Ok(json) = serde_json::to_string(&StateLine {
+                    record_type: "state",
+                    state: &state,
+                }) {
+                    let _ = sink.write_line(&json).await;
+                    let _ = sink.flush().await;
+                }
+            }
+            RolloutCmd::RecordTurnTiming(timing) => {
+                #[derive(Serialize)]
+                struct TurnTimingLine<'a> {
+                    record_type: &'static str,
+                    #[serde(flatten)]
+                    timing: &'a TurnTiming,
+                }
+                if let Ok(json) = serde_json::to_string(&TurnTimingLine {
+                    record_type: "turn_timing",
+                    timing: &timing,
+                }) {
+                    let _ = sink.write_line(&json).await;
+                    let _ = sink.flush().await;
+                }
+            }
+            RolloutCmd::RecordSummary(summary) => {
+                #[derive(Serialize)]
+                struct SummaryLine<'a> {
+                    r#type: &'static str,
+                    #[serde(flatten)]
+                    summary: &'a SessionSummaryFooter,
+                }
+                if let Ok(json) = serde_json::to_string(&SummaryLine {
+                    r#type: SESSION_SUMMARY_TYPE,
+                    summary: &summary,
+                }) {
+                    let _ = sink.write_line(&json).await;
+                    let _ = sink.flush().await;
+                }
+            }
+            RolloutCmd::Shutdown => break,
+        }
+    }
+
+    // Every sender is gone or a shutdown was requested, so the rollout is
+    // complete: the file is append-only, which means the digest can only be
+    // computed now.
+    rx.close();
+    let _ = sink.finish().await;
+    if let Some(path) = integrity_path {
+        if let Err(e) = write_integrity_sidecar(&path).await {
+            warn!("failed to write rollout digest for {path:?}: {e}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::expect_used, clippy::unwrap_used)]
+    use super::*;
+    use crate::models::ContentItem;
+    use pretty_assertions::assert_eq;
+    use tempfile::TempDir;
+
+    fn user_message(text: &str) -> ResponseItem {
+        ResponseItem::Message {
+            role: "user".to_string(),
+            content: vec![ContentItem::InputText {
+                text: text.to_string(),
+            }],
+            name: None,
+        }
+    }
+
+    /// Runs `record` against a recorder backed by a fresh rollout file and
+    /// waits for the writer to drain before returning the file path.
+    async fn write_rollout<F, Fut>(dir: &TempDir, record: F) -> PathBuf
+    where
+        F: FnOnce(RolloutRecorder) -> Fut,
+        Fut: std::future::Future<Output = ()>,
+    {
+        write_rollout_with(dir, WriteOptions::default(), record).await
+    }
+
+    #[derive(Default)]
+    struct WriteOptions {
+        integrity_hash: bool,
+        persist_reasoning: bool,
+        gzip: bool,
+        max_bytes: Option<u64>,
+        dedupe_outputs: bool,
+    }
+
+    async fn write_rollout_with<F, Fut>(dir: &TempDir, options: WriteOptions, record: F) -> PathBuf
+    where
+        F: FnOnce(RolloutRecorder) -> Fut,
+        Fut: std::future::Future<Output = ()>,
+    {
+        let WriteOptions {
+            integrity_hash,
+            persist_reasoning,
+            gzip,
+            max_bytes,
+            dedupe_outputs,
+        } = options;
+        let file_name = if gzip {
+            "rollout.jsonl.gz"
+        } else {
+            "rollout.jsonl"
+        };
+        let path = dir.path().join(file_name);
+        let file = std::fs::OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(&path)
+            .unwrap();
+        let meta = SessionMeta {
+            id: Uuid::new_v4(),
+            timestamp: "2025-01-01T00:00:00.000Z".to_string(),
+            instructions: None,
+            ..Default::default()
+        };
+        let rotation = max_bytes.map(|max_bytes| Rotation {
+            max_bytes,
+            first_path: path.clone(),
+            part: 1,
+            meta: meta.clone(),
+            part_has_entries: false,
+        });
+        let recorder = RolloutRecorder::spawn(
+            WriterSink::File(FileRolloutSink::new(tokio::fs::File::from_std(file), gzip)),
+            meta.id,
+            Some(meta),
+            integrity_hash.then(|| path.clone()),
+            rotation,
+            persist_reasoning,
+            dedupe_outputs,
+        );
+        let writer = recorder.writer.clone();
+        record(recorder).await;
+        let writer = writer.lock().unwrap().take().unwrap();
+        writer.await.unwrap();
+        path
+    }
+
+    #[tokio::test]
+    async fn labeled_and_unlabeled_items_round_trip() {
+        let dir = TempDir::new().unwrap();
+        let labels = HashMap::from([("source".to_string(), "retry".to_string())]);
+        let path = write_rollout(&dir, |recorder| {
+            let labels = labels.clone();
+            async move {
+                recorder
+                    .record_items(&[user_message("plain")])
+                    .await
+                    .unwrap();
+                recorder
+                    .record_item_with_meta(&user_message("tagged"), labels)
+                    .await
+                    .unwrap();
+            }
+        })
+        .await;
+
+        let text = std::fs::read_to_string(&path).unwrap();
+        let tagged_line: Value = serde_json::from_str(text.lines().nth(2).unwrap()).unwrap();
+        assert_eq!(tagged_line["labels"]["source"], "retry");
+        assert_eq!(tagged_line["item"]["type"], "message");
+
+        let (_recorder, saved) = RolloutRecorder::resume(&test_config(&dir), &path)
+            .await
+            .unwrap();
+        assert_eq!(
+            serde_json::to_value(&saved.items).unwrap(),
+            serde_json::to_value(vec![user_message("plain"), user_message("tagged")]).unwrap()
+        );
+        assert_eq!(saved.item_labels, vec![HashMap::new(), labels]);
+    }
+
+    #[tokio::test]
+    async fn labeled_repeated_output_is_recorded_as_labeled_ref() {
+        let dir = TempDir::new().unwrap();
+        let output = |call_id: &str| ResponseItem::FunctionCallOutput {
+            call_id: call_id.to_string(),
+            output: crate::models::FunctionCallOutputPayload {
+                content: "Finished".to_string(),
+                success: Some(true),
+                exit_code: None,
+                duration_ms: None,
+            },
+        };
+        let labels = HashMap::from([("source".to_string(), "retry".to_string())]);
+        let options = WriteOptions {
+            dedupe_outputs: true,
+            ..Default::default()
+        };
+        let path = write_rollout_with(&dir, options, |recorder| {
+            let items = [output("call1"), output("call2")];
+            let labels = labels.clone();
+            async move {
+                recorder.record_items(&items[..1]).await.unwrap();
+                recorder
+                    .record_item_with_meta(&items[1], labels)
+                    .await
+                    .unwrap();
+            }
+        })
+        .await;
+
+        let text = std::fs::read_to_string(&path).unwrap();
+        let tagged_line: Value = serde_json::from_str(text.lines().nth(2).unwrap()).unwrap();
+        assert_eq!(tagged_line["labels"]["source"], "retry");
+        assert_eq!(tagged_line["item"]["type"], OUTPUT_REF_TYPE);
+
+        let (_recorder, saved) = RolloutRecorder::resume(&test_config(&dir), &path)
+            .await
+            .unwrap();
+        assert_eq!(saved.items, vec![output("call1"), output("call2")]);
+        assert_eq!(saved.item_labels, vec![HashMap::new(), labels]);
+    }
+
+    #[tokio::test]
+    async fn read_session_returns_recorded_items() {
+        let dir = TempDir::new().unwrap();
+        let items = vec![
+            user_message("list files"),
+            ResponseItem::FunctionCall {
+                name: "shell".to_string(),
+                arguments: r#"{"command":["ls"]}"#.to_string(),
+                call_id: "call1".to_string(),
+            },
+            ResponseItem::FunctionCallOutput {
+                call_id: "call1".to_string(),
+                output: crate::models::FunctionCallOutputPayload {
+                    content: "Cargo.toml".to_string(),
+                    success: Some(true),
+                    exit_code: Some(0),
+                    duration_ms: Some(12),
+                },
+            },
+        ];
+        let path = write_rollout(&dir, |recorder| {
+            let items = items.clone();
+            async move {
+                recorder.record_items(&items).await.unwrap();
+                recorder
+                    .record_state(SessionStateSnapshot::default())
+                    .await
+                    .unwrap();
+            }
+        })
+        .await;
+
+        let (meta, read, _summary) = RolloutRecorder::read_session(&path).unwrap();
+
+        assert_eq!(meta.timestamp, "2025-01-01T00:00:00.000Z");
+        // Unlike the wire format, the rollout keeps the output's metadata.
+        assert_eq!(read, items);
+        let text = std::fs::read_to_string(&path).unwrap();
+        let output_line: Value = serde_json::from_str(text.lines().nth(3).unwrap()).unwrap();
+        assert_eq!(
+            output_line["output"],
+            serde_json::json!({
+                "content": "Cargo.toml",
+                "success": true,
+                "exit_code": 0,
+                "duration_ms": 12,
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn large_function_call_output_round_trips() {
+        let dir = TempDir::new().unwrap();
+        let large = ResponseItem::FunctionCallOutput {
+            call_id: "call1".to_string(),
+            output: crate::models::FunctionCallOutputPayload {
+                content: "log line \"quoted\"\n".repeat(LARGE_ITEM_BYTES / 10),
+                success: None,
+                exit_code: None,
+                duration_ms: None,
+            },
+        };
+        assert!(large_item_bytes(&large).is_some());
+        let items = vec![user_message("before"), large, user_message("after")];
+        let path = write_rollout(&dir, |recorder| {
+            let items = items.clone();
+            async move {
+                recorder.record_items(&items).await.unwrap();
+            }
+        })
+        .await;
+
+        let (_meta, read, _summary) = RolloutRecorder::read_session(&path).unwrap();
+        assert_eq!(read, items);
+    }
+
+    #[tokio::test]
+    async fn reasoning_items_are_persisted_only_when_enabled() {
+        let reasoning = ResponseItem::Reasoning {
+            id: "rs_1".to_string(),
+            summary: vec![crate::models::ReasoningItemReasoningSummary::SummaryText {
+                text: "Looking at the tests first.".to_string(),
+            }],
+        };
+        for persist_reasoning in [false, true] {
+            let dir = TempDir::new().unwrap();
+            let items = vec![user_message("fix the bug"), reasoning.clone()];
+            let options = WriteOptions {
+                persist_reasoning,
+                ..Default::default()
+            };
+            let path = write_rollout_with(&dir, options, |recorder| async move {
+                recorder.record_items(&items).await.unwrap();
+            })
+            .await;
+
+            let (_meta, read, _summary) = RolloutRecorder::read_session(&path).unwrap();
+            let types: Vec<&str> = read
+                .iter()
+                .map(|item| match item {
+                    ResponseItem::Reasoning { .. } => "reasoning",
+                    _ => "other",
+                })
+                .collect();
+            let expected = if persist_reasoning {
+                vec!["other", "reasoning"]
+            } else {
+                vec!["other"]
+            };
+            assert_eq!(types, expected, "persist_reasoning = {persist_reasoning}");
+        }
+    }
+
+    #[tokio::test]
+    async fn repeated_function_outputs_are_recorded_as_refs() {
+        let dir = TempDir::new().unwrap();
+        let output = |call_id: &str| ResponseItem::FunctionCallOutput {
+            call_id: call_id.to_string(),
+            output: crate::models::FunctionCallOutputPayload {
+                content: "Compiling codex-core\nFinished".to_string(),
+                success: Some(true),
+                exit_code: None,
+                duration_ms: None,
+            },
+        };
+        let items = vec![output("call1"), output("call2")];
+        let options = WriteOptions {
+            dedupe_outputs: true,
+            ..Default::default()
+        };
+        let path = write_rollout_with(&dir, options, |recorder| {
+            let items = items.clone();
+            async move {
+                recorder.record_items(&items).await.unwrap();
+            }
+        })
+        .await;
+
+        let text = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<Value> = text
+            .lines()
+            .skip(1)
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["type"], "function_call_output");
+        assert_eq!(
+            lines[1],
+            serde_json::json!({
+                "type": OUTPUT_REF_TYPE,
+                "call_id": "call2",
+                "hash": output_digest("Compiling codex-core\nFinished"),
+                "success": true,
+            })
+        );
+
+        let (_meta, read, _summary) = RolloutRecorder::read_session(&path).unwrap();
+        assert_eq!(read, items);
+    }
+
+    #[tokio::test]
+    async fn session_summary_footer_round_trips() {
+        let dir = TempDir::new().unwrap();
+        let footer = SessionSummaryFooter {
+            input_tokens: 1200,
+            output_tokens: 300,
+            total_tokens: 1500,
+            turns: 2,
+        };
+        let path = write_rollout(&dir, |recorder| {
+            let footer = footer.clone();
+            async move {
+                recorder
+                    .record_items(&[user_message("hello")])
+                    .await
+                    .unwrap();
+                recorder.record_summary(footer).await.unwrap();
+            }
+        })
+        .await;
+
+        let text = std::fs::read_to_string(&path).unwrap();
+        let last: Value = serde_json::from_str(text.lines().last().unwrap()).unwrap();
+        assert_eq!(last["type"], "session_summary");
+        assert_eq!(last["turns"], 2);
+
+        let (_meta, items, summary) = RolloutRecorder::read_session(&path).unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(summary, Some(footer.clone()));
+
+        let (_recorder, saved) = RolloutRecorder::resume(&test_config(&dir), &path)
+            .await
+            .unwrap();
+        assert_eq!(saved.items.len(), 1);
+        assert_eq!(saved.summary, Some(footer));
+    }
+
+    #[tokio::test]
+    async fn turn_timing_round_trips_as_turn_metadata() {
+        let dir = TempDir::new().unwrap();
+        let timing = TurnTiming {
+            time_to_first_token_ms: Some(420),
+            total_ms: 1830,
+            input_items: 3,
+            input_tokens: Some(1200),
+        };
+        let path = write_rollout(&dir, |recorder| {
+            let timing = timing.clone();
+            async move {
+                recorder
+                    .record_items(&[user_message("hello")])
+                    .await
+                    .unwrap();
+                recorder.record_turn_timing(timing).await.unwrap();
+            }
+        })
+        .await;
+
+        let (_recorder, saved) = RolloutRecorder::resume(&test_config(&dir), &path)
+            .await
+            .unwrap();
+        assert_eq!(saved.turn_timings, vec![timing]);
+        assert_eq!(saved.items.len(), 1);
+
+        let (_meta, items, _summary) = RolloutRecorder::read_session(&path).unwrap();
+        assert_eq!(items.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn gzip_rollout_round_trips() {
+        let dir = TempDir::new().unwrap();
+        let options = WriteOptions {
+            gzip: true,
+            ..Default::default()
+        };
+        let path = write_rollout_with(&dir, options, |recorder| async move {
+            recorder
+                .record_items(&[user_message("first")])
+                .await
+                .unwrap();
+            recorder
+                .record_items(&[user_message("second")])
+                .await
+                .unwrap();
+        })
+        .await;
+
+        let raw = std::fs::read(&path).unwrap();
+        assert_eq!(
+            &raw[..2],
+            &[0x1f, 0x8b],
+            "file should start with gzip magic"
+        );
+
+        let (meta, items, _summary) = RolloutRecorder::read_session(&path).unwrap();
+        assert_eq!(meta.timestamp, "2025-01-01T00:00:00.000Z");
+        assert_eq!(
+            serde_json::to_value(&items).unwrap(),
+            serde_json::to_value(vec![user_message("first"), user_message("second")]).unwrap()
+        );
+        assert_eq!(latest_session_in(dir.path()).unwrap(), Some(path));
+    }
+
+    #[tokio::test]
+    async fn rotated_rollout_is_read_and_listed_as_one_session() {
+        let dir = TempDir::new().unwrap();
+        // The header alone fits, but not the header plus one item, so the
+        // second item starts a new part.
+        let options = WriteOptions {
+            max_bytes: Some(200),
+            ..Default::default()
+        };
+        let path = write_rollout_with(&dir, options, |recorder| async move {
+            recorder
+                .record_items(&[user_message("first part")])
+                .await
+                .unwrap();
+            recorder
+                .record_items(&[user_message("second part")])
+                .await
+                .unwrap();
+        })
+        .await;
+
+        let second = dir.path().join("rollout-part02.jsonl");
+        assert_eq!(session_parts(&path), vec![path.clone(), second.clone()]);
+        assert!(!dir.path().join("rollout-part03.jsonl").exists());
+        let first_text = fs::read_to_string(&path).unwrap();
+        let second_text = fs::read_to_string(&second).unwrap();
+        assert_eq!(first_text.lines().count(), 2);
+        assert_eq!(second_text.lines().count(), 2);
+        assert_eq!(first_text.lines().next(), second_text.lines().next());
+
+        let (_meta, items, _summary) = RolloutRecorder::read_session(&path).unwrap();
+        assert_eq!(
+            serde_json::to_value(&items).unwrap(),
+            serde_json::to_value(vec![
+                user_message("first part"),
+                user_message("second part")
+            ])
+            .unwrap()
+        );
+        let sessions = list_sessions_in(dir.path()).unwrap();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].path, path);
+    }
+
+    #[test]
+    fn part_paths_are_derived_from_the_first_part() {
+        let first = Path::new("/s/rollout-2025-01-01T00-00-00-abc.jsonl.gz");
+        let third = part_path(first, 3);
+        assert_eq!(
+            third,
+            Path::new("/s/rollout-2025-01-01T00-00-00-abc-part03.jsonl.gz")
+        );
+        assert_eq!(part_number(&third), 3);
+        assert_eq!(part_number(first), 1);
+        assert_eq!(part_path(&third, 1), first);
+    }
+
+    #[tokio::test]
+    async fn rollout_without_rollout_extension_resumes_as_one_part() {
+        let dir = TempDir::new().unwrap();
+        let path = write_session_header(dir.path(), "session.json", "2025-01-01T00:00:00.000Z");
+        let mut text = fs::read_to_string(&path).unwrap();
+        text.push_str(&serde_json::to_string(&user_message("hello")).unwrap());
+        text.push('\n');
+        fs::write(&path, text).unwrap();
+
+        assert_eq!(session_parts(&path), vec![path.clone()]);
+        let (_recorder, saved) = RolloutRecorder::resume(&test_config(&dir), &path)
+            .await
+            .unwrap();
+        assert_eq!(saved.items.len(), 1);
+        let (_meta, items, _summary) = RolloutRecorder::read_session(&path).unwrap();
+        assert_eq!(items.len(), 1);
+    }
+
+    #[test]
+    fn read_session_reports_malformed_lines() {
+        let dir = TempDir::new().unwrap();
+        let path = write_session_header(dir.path(), "rollout.jsonl", "2025-01-01T00:00:00.000Z");
+        let mut text = fs::read_to_string(&path).unwrap();
+        text.push_str("\n{\"type\": \"message\"\n");
+        fs::write(&path, text).unwrap();
+
+        let err = RolloutRecorder::read_session(&path).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("rollout.jsonl:3: invalid rollout line"),
+            "{err}"
+        );
+        match err {
+            RolloutReadError::BadItem { line, snippet, .. } => {
+                assert_eq!(line, 3);
+                assert_eq!(snippet, "{\"type\": \"message\"");
+            }
+            other => panic!("expected BadItem, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn read_session_distinguishes_missing_files_and_bad_headers() {
+        let dir = TempDir::new().unwrap();
+        let missing = dir.path().join("missing.jsonl");
+        assert!(matches!(
+            RolloutRecorder::read_session(&missing),
+            Err(RolloutReadError::NotFound { path }) if path == missing
+        ));
+
+        let bad = dir.path().join("bad.jsonl");
+        fs::write(&bad, "not a header\n").unwrap();
+        assert!(matches!(
+            RolloutRecorder::read_session(&bad),
+            Err(RolloutReadError::BadHeader { .. })
+        ));
+    }
+
+    #[test]
+    fn snippet_shortens_long_lines() {
+        assert_eq!(snippet("short"), "short");
+        let long = "é".repeat(SNIPPET_CHARS + 5);
+        assert_eq!(snippet(&long), format!("{}…", "é".repeat(SNIPPET_CHARS)));
+    }
+
+    fn test_config(codex_home: &TempDir) -> Config {
+        Config::load_from_base_config_with_overrides(
+            crate::config::ConfigToml::default(),
+            crate::config::ConfigOverrides::default(),
+            codex_home.path().to_path_buf(),
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn resume_appends_without_touching_header() {
+        let dir = TempDir::new().unwrap();
+        let path = write_rollout(&dir, |recorder| async move {
+            recorder
+                .record_items(&[user_message("before")])
+                .await
+                .unwrap();
+        })
+        .await;
+        let original = fs::read_to_string(&path).unwrap();
+        let original_header = original.lines().next().unwrap().to_string();
+        let original_meta: SessionMeta = serde_json::from_str(&original_header).unwrap();
+
+        let config = test_config(&dir);
+        let (recorder, saved) = RolloutRecorder::resume(&config, &path).await.unwrap();
+        assert_eq!(saved.session_id, original_meta.id);
+        recorder
+            .record_items(&[user_message("after")])
+            .await
+            .unwrap();
+        drop(recorder);
+
+        // The writer task drains asynchronously once the recorder is dropped.
+        let deadline = tokio::time::Instant::now() + std::time::Duration::from_secs(5);
+        let mut text = fs::read_to_string(&path).unwrap();
+        while text.lines().count() < 3 && tokio::time::Instant::now() < deadline {
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            text = fs::read_to_string(&path).unwrap();
+        }
+
+        assert!(text.starts_with(&original));
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], original_header);
+        let (meta, items, _summary) = RolloutRecorder::read_session(&path).unwrap();
+        assert_eq!(meta.timestamp, original_meta.timestamp);
+        assert_eq!(
+            serde_json::to_value(&items).unwrap(),
+            serde_json::to_value(vec![user_message("before"), user_message("after")]).unwrap()
+        );
+    }
+
+    fn write_session_header(dir: &Path, name: &str, timestamp: &str) -> PathBuf {
+        fs::create_dir_all(dir).unwrap();
+        let path = dir.join(name);
+        let meta = SessionMeta {
+            id: Uuid::new_v4(),
+            timestamp: timestamp.to_string(),
+            instructions: None,
+            ..Default::default()
+        };
+        fs::write(
+            &path,
+            format!("{}\n", serde_json::to_string(&meta).unwrap()),
+        )
+        .unwrap();
+        path
+    }
+
+    #[tokio::test]
+    async fn header_records_model_provider_and_cwd() {
+        let codex_home = TempDir::new().unwrap();
+        let config = test_config(&codex_home);
+        let recorder = RolloutRecorder::new(&config, Uuid::new_v4(), None, None)
+            .await
+            .unwrap();
+        recorder.shutdown().await.unwrap();
+
+        let path = latest_session(&config).unwrap().unwrap();
+        let header: Value =
+            serde_json::from_str(fs::read_to_string(&path).unwrap().lines().next().unwrap())
+                .unwrap();
+        assert_eq!(header["model"], config.model.as_str());
+        assert_eq!(header["provider"], config.model_provider_id.as_str());
+        assert_eq!(header["cwd"], config.cwd.display().to_string().as_str());
+    }
+
+    #[tokio::test]
+    async fn header_records_git_branch_and_dirty_state() {
+        let codex_home = TempDir::new().unwrap();
+        let repo = TempDir::new().unwrap();
+        for args in [
+            &["init", "-q"][..],
+            &["symbolic-ref", "HEAD", "refs/heads/triage"],
+        ] {
+            let status = std::process::Command::new("git")
+                .args(args)
+                .current_dir(repo.path())
+                .status()
+                .unwrap();
+            assert!(status.success());
+        }
+        fs::write(repo.path().join("scratch.txt"), "wip").unwrap();
+        let mut config = test_config(&codex_home);
+        config.cwd = repo.path().to_path_buf();
+
+        let recorder = RolloutRecorder::new(&config, Uuid::new_v4(), None, None)
+            .await
+            .unwrap();
+        recorder.shutdown().await.unwrap();
+
+        let path = latest_session(&config).unwrap().unwrap();
+        let header: Value =
+            serde_json::from_str(fs::read_to_string(&path).unwrap().lines().next().unwrap())
+                .unwrap();
+        assert_eq!(
+            header["git"],
+            serde_json::json!({"branch": "triage", "is_dirty": true})
+        );
+    }
+
+    #[test]
+    fn header_without_model_provider_and_cwd_still_loads() {
+        let meta: SessionMeta = serde_json::from_str(
+            r#"{"id":"5973b6c0-94b8-487b-a530-2aeb6098ae0e","timestamp":"2025-05-07T17:24:21.123Z","instructions":null}"#,
+        )
+        .unwrap();
+        assert_eq!(meta.model, "");
+        assert_eq!(meta.provider, "");
+        assert_eq!(meta.cwd, "");
+    }
+
+    /// Keeps written lines in memory; they become visible on flush.
+    #[derive(Clone, Default)]
+    struct MemorySink {
+        pending: Vec<String>,
+        flushed: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl RolloutSink for MemorySink {
+        fn write_line<'a>(&'a mut self, line: &'a str) -> SinkFuture<'a> {
+            self.pending.push(line.to_string());
+            Box::pin(async { Ok(()) })
+        }
+
+        fn flush(&mut self) -> SinkFuture<'_> {
+            let pending = std::mem::take(&mut self.pending);
+            self.flushed.lock().unwrap().extend(pending);
+            Box::pin(async { Ok(()) })
+        }
+    }
+
+    #[tokio::test]
+    async fn custom_sink_receives_every_line() {
+        let codex_home = TempDir::new().unwrap();
+        let config = test_config(&codex_home);
+        let sink = MemorySink::default();
+        let session_id = Uuid::new_v4();
+        let recorder =
+            RolloutRecorder::new(&config, session_id, None, Some(Box::new(sink.clone())))
+                .await
+                .unwrap();
+        recorder
+            .record_items(&[user_message("hello")])
+            .await
+            .unwrap();
+        recorder.shutdown().await.unwrap();
+
+        let lines = sink.flushed.lock().unwrap().clone();
+        assert_eq!(lines.len(), 2, "{lines:?}");
+        let meta: SessionMeta = serde_json::from_str(&lines[0]).unwrap();
+        assert_eq!(meta.id, session_id);
+        let item: Value = serde_json::from_str(&lines[1]).unwrap();
+        assert_eq!(item, serde_json::to_value(user_message("hello")).unwrap());
+        assert!(latest_session(&config).unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn shutdown_flushes_items_recorded_just_before() {
+        let codex_home = TempDir::new().unwrap();
+        let config = test_config(&codex_home);
+        let recorder = RolloutRecorder::new(&config, Uuid::new_v4(), None, None)
+            .await
+            .unwrap();
+        let clone = recorder.clone();
+
+        recorder
+            .record_items(&[user_message("last words")])
+            .await
+            .unwrap();
+        recorder.shutdown().await.unwrap();
+
+        let path = latest_session(&config).unwrap().unwrap();
+        let (_meta, items, _summary) = RolloutRecorder::read_session(&path).unwrap();
+        assert_eq!(
+            serde_json::to_value(&items).unwrap(),
+            serde_json::to_value(vec![user_message("last words")]).unwrap()
+        );
+        assert!(clone.record_items(&[user_message("late")]).await.is_err());
+        clone.shutdown().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn recorder_falls_back_to_utc_without_local_time() {
+        let codex_home = TempDir::new().unwrap();
+        let config = test_config(&codex_home);
+
+        let recorder =
+            RolloutRecorder::new_with_clock(&config, Uuid::new_v4(), None, None, &NoLocalTime)
+                .await
+                .unwrap();
+        recorder
+            .record_items(&[user_message("hello")])
+            .await
+            .unwrap();
+        recorder.shutdown().await.unwrap();
+
+        let path = latest_session(&config).unwrap().unwrap();
+        let (meta, items, _summary) = RolloutRecorder::read_session(&path).unwrap();
+        assert!(meta.timestamp.ends_with('Z'));
+        assert_eq!(items.len(), 1);
+    }
+
+    /// A clock whose UTC offset is unknown, as in containers without
+    /// timezone data.
+    struct NoLocalTime;
+
+    impl Clock for NoLocalTime {
+        fn now_utc(&self) -> OffsetDateTime {
+            OffsetDateTime::now_utc()
+        }
+
+        fn now_local(&self) -> Result<OffsetDateTime, IndeterminateOffset> {
+            Err(IndeterminateOffset)
+        }
+    }
+
+    /// A clock stopped at a fixed time, in both UTC and local time.
+    struct FixedClock(OffsetDateTime);
+
+    impl Clock for FixedClock {
+        fn now_utc(&self) -> OffsetDateTime {
+            self.0
+        }
+
+        fn now_local(&self) -> Result<OffsetDateTime, IndeterminateOffset> {
+            Ok(self.0)
+        }
+    }
+
+    #[test]
+    fn utc_option_ignores_local_time() {
+        struct UtcOnly;
+
+        impl Clock for UtcOnly {
+            fn now_utc(&self) -> OffsetDateTime {
+                OffsetDateTime::UNIX_EPOCH
+            }
+
+            fn now_local(&self) -> Result<OffsetDateTime, IndeterminateOffset> {
+                panic!("local time should not be consulted");
+            }
+        }
+
+        assert_eq!(
+            session_start_time(true, &UtcOnly),
+            OffsetDateTime::UNIX_EPOCH
+        );
+    }
+
+    #[tokio::test]
+    async fn fixed_clock_determines_filename_and_header_timestamp() {
+        let codex_home = TempDir::new().unwrap();
+        let config = test_config(&codex_home);
+        let session_id = Uuid::parse_str("5973b6c0-94b8-487b-a530-2aeb6098ae0e").unwrap();
+        let clock = FixedClock(time::macros::datetime!(2025-05-07 17:24:21.123 UTC));
+
+        let recorder = RolloutRecorder::new_with_clock(&config, session_id, None, None, &clock)
+            .await
+            .unwrap();
+        recorder.shutdown().await.unwrap();
+
+        let path = latest_session(&config).unwrap().unwrap();
+        assert_eq!(
+            path,
+            codex_home.path().join(format!(
+                "{SESSIONS_SUBDIR}/2025/05/07/rollout-2025-05-07T17-24-21-{session_id}.jsonl"
+            ))
+        );
+        let (meta, _items, _summary) = RolloutRecorder::read_session(&path).unwrap();
+        assert_eq!(meta.timestamp, "2025-05-07T17:24:21.123Z");
+    }
+
+    #[test]
+    fn rollout_dir_overrides_sessions_dir() {
+        let codex_home = TempDir::new().unwrap();
+        let scratch = TempDir::new().unwrap();
+        let mut config = test_config(&codex_home);
+        config.rollout_dir = Some(scratch.path().to_path_buf());
+
+        let LogFileInfo { path, .. } =
+            create_log_file(&config, Uuid::new_v4(), OffsetDateTime::now_utc()).unwrap();
+
+        assert!(path.starts_with(scratch.path()));
+        assert!(path.exists());
+        assert!(!codex_home.path().join(SESSIONS_SUBDIR).exists());
+    }
+
+    #[test]
+    fn latest_session_picks_newest_header_timestamp() {
+        let dir = TempDir::new().unwrap();
+        let sessions = dir.path().join(SESSIONS_SUBDIR);
+        write_session_header(
+            &sessions.join("2025/01/02"),
+            "rollout-a.jsonl",
+            "2025-01-02T09:00:00.000Z",
+        );
+        let newest = write_session_header(
+            &sessions.join("2025/01/02"),
+            "rollout-b.jsonl",
+            "2025-01-02T10:00:00.000Z",
+        );
+        // Written last, so it has the newest mtime, but its header is older.
+        write_session_header(
+            &sessions.join("2025/01/01"),
+            "rollout-c.jsonl",
+            "2025-01-01T23:00:00.000Z",
+        );
+        fs::write(sessions.join("2025/01/02/rollout-bad.jsonl"), "not json\n").unwrap();
+        fs::write(sessions.join("2025/01/02/rollout-empty.jsonl"), "").unwrap();
+
+        assert_eq!(Some(newest), latest_session_in(&sessions).unwrap());
+    }
+
+    #[test]
+    fn list_sessions_is_newest_first_and_skips_corrupt_files() {
+        let codex_home = TempDir::new().unwrap();
+        let day = codex_home.path().join(SESSIONS_SUBDIR).join("2025/01/02");
+        fs::create_dir_all(&day).unwrap();
+        let older_meta = SessionMeta {
+            id: Uuid::new_v4(),
+            timestamp: "2025-01-02T08:00:00.000Z".to_string(),
+            instructions: Some("Always answer in haiku.\nNever use tabs.".to_string()),
+            ..Default::default()
+        };
+        let newer_meta = SessionMeta {
+            id: Uuid::new_v4(),
+            timestamp: "2025-01-02T09:00:00.000Z".to_string(),
+            instructions: None,
+            ..Default::default()
+        };
+        let older = day.join("rollout-older.jsonl");
+        let newer = day.join("rollout-newer.jsonl");
+        for (path, meta) in [(&older, &older_meta), (&newer, &newer_meta)] {
+            fs::write(path, format!("{}\n", serde_json::to_string(meta).unwrap())).unwrap();
+        }
+        fs::write(day.join("rollout-empty.jsonl"), "").unwrap();
+        fs::write(day.join("rollout-corrupt.jsonl"), "{not json\n").unwrap();
+
+        let sessions = RolloutRecorder::list_sessions(&test_config(&codex_home)).unwrap();
+
+        assert_eq!(
+            sessions,
+            vec![
+                SessionSummary {
+                    id: newer_meta.id,
+                    timestamp: newer_meta.timestamp,
+                    instructions_preview: None,
+                    path: newer,
+                },
+                SessionSummary {
+                    id: older_meta.id,
+                    timestamp: older_meta.timestamp,
+                    instructions_preview: Some("Always answer in haiku.".to_string()),
+                    path: older,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn instructions_preview_is_truncated() {
+        let long = "x".repeat(INSTRUCTIONS_PREVIEW_CHARS + 10);
+        let preview = preview(&long);
+        assert_eq!(preview.chars().count(), INSTRUCTIONS_PREVIEW_CHARS);
+        assert!(preview.ends_with('…'));
+    }
+
+    #[test]
+    fn latest_session_without_sessions_dir_is_none() {
+        let dir = TempDir::new().unwrap();
+        assert_eq!(
+            None,
+            latest_session_in(&dir.path().join(SESSIONS_SUBDIR)).unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn integrity_digest_detects_tampering() {
+        let dir = TempDir::new().unwrap();
+        let path = write_rollout_with(
+            &dir,
+            WriteOptions {
+                integrity_hash: true,
+                ..Default::default()
+            },
+            |recorder| async move {
+                recorder
+                    .record_items(&[user_message("original")])
+                    .await
+                    .unwrap();
+            },
+        )
+        .await;
+
+        assert!(integrity_sidecar_path(&path).exists());
+        assert!(verify_rollout_integrity(&path).await.unwrap());
+
+        let tampered = std::fs::read_to_string(&path)
+            .unwrap()
+            .replace("original", "modified");
+        std::fs::write(&path, tampered).unwrap();
+        assert!(!verify_rollout_integrity(&path).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn verify_fails_without_sidecar() {
+        let dir = TempDir::new().unwrap();
+        let path = write_rollout(&dir, |recorder| async move {
+            recorder.record_items(&[user_message("hi")]).await.unwrap();
+        })
+        .await;
+
+        assert!(!integrity_sidecar_path(&path).exists());
+        assert!(verify_rollout_integrity(&path).await.is_err());
     }
 }
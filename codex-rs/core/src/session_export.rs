@@ -0,0 +1,538 @@
+//! Render a recorded session as a standalone HTML or Markdown transcript, or
+//! as fine-tuning data.
+//!
+//! The HTML output has no external dependencies: styles are inlined and images
+//! are embedded straight from the `data:` URLs stored in the rollout, so the
+//! file can be attached to a bug report or shared with people who do not have
+//! Codex installed. The Markdown output is meant to be pasted into an issue.
+
+use std::fmt::Write as _;
+
+use serde_json::Value;
+use serde_json::json;
+
+use crate::models::ContentItem;
+use crate::models::LocalShellAction;
+use crate::models::ReasoningItemReasoningSummary;
+use crate::models::ResponseItem;
+use crate::rollout::SessionMeta;
+
+const STYLE: &str = "\
+body { font-family: system-ui, sans-serif; max-width: 60rem; margin: 2rem auto; padding: 0 1rem; }
+.item { border-left: 4px solid #ccc; margin: 1rem 0; padding: 0.25rem 1rem; }
+.role-user { border-color: #2563eb; }
+.role-assistant { border-color: #16a34a; }
+.tool { border-color: #9333ea; }
+.reasoning { border-color: #ca8a04; color: #555; }
+.role { font-weight: bold; text-transform: capitalize; }
+pre { background: #f5f5f5; padding: 0.75rem; overflow-x: auto; }
+img { max-width: 100%; }
+";
+
+/// Renders `items` as a self-contained HTML document titled with the session
+/// id from `meta`.
+pub fn export_html(meta: &SessionMeta, items: &[ResponseItem]) -> String {
+    let mut html = String::new();
+    let id = meta.id.to_string();
+    html.push_str("<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n");
+    let _ = writeln!(html, "<title>Codex session {id}</title>");
+    let _ = writeln!(html, "<style>\n{STYLE}</style>\n</head>\n<body>");
+    let _ = writeln!(html, "<h1>Codex session {id}</h1>");
+    let _ = writeln!(
+        html,
+        "<p class=\"timestamp\">{}</p>",
+        escape_html(&meta.timestamp)
+    );
+
+    for item in items {
+        render_item(&mut html, item);
+    }
+
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+fn render_item(html: &mut String, item: &ResponseItem) {
+    match item {
+        ResponseItem::Message { role, content, .. } => {
+            let role = escape_html(role);
+            let _ = writeln!(
+                html,
+                "<div class=\"item role-{role}\">\n<div class=\"role\">{role}</div>"
+            );
+            for content_item in content {
+                match content_item {
+                    ContentItem::InputText { text } | ContentItem::OutputText { text } => {
+                        render_text(html, text);
+                    }
+                    ContentItem::InputImage { image_url, .. } => render_image(html, image_url),
+                }
+            }
+            html.push_str("</div>\n");
+        }
+        ResponseItem::Reasoning { summary, .. } => {
+            html.push_str("<div class=\"item reasoning\">\n<div class=\"role\">reasoning</div>\n");
+            for ReasoningItemReasoningSummary::SummaryText { text } in summary {
+                render_text(html, text);
+            }
+            html.push_str("</div>\n");
+        }
+        ResponseItem::LocalShellCall { action, .. } => {
+            let LocalShellAction::Exec(exec) = action;
+            let _ = writeln!(
+                html,
+                "<div class=\"item tool\">\n<div class=\"role\">shell</div>\n<pre><code class=\"language-sh\">{}</code></pre>\n</div>",
+                escape_html(&exec.command.join(" "))
+            );
+        }
+        ResponseItem::FunctionCall {
+            name, arguments, ..
+        } => {
+            let _ = writeln!(
+                html,
+                "<div class=\"item tool\">\n<div class=\"role\">{}</div>\n<pre><code class=\"language-json\">{}</code></pre>\n</div>",
+                escape_html(name),
+                escape_html(arguments)
+            );
+        }
+        ResponseItem::FunctionCallOutput { output, .. } => {
+            let _ = writeln!(
+                html,
+                "<div class=\"item tool\">\n<div class=\"role\">output</div>\n<pre><code>{}</code></pre>\n</div>",
+                escape_html(&output.content)
+            );
+        }
+        ResponseItem::Other => {}
+    }
+}
+
+/// Renders message text, turning fenced code blocks into `<pre><code>` with a
+/// `language-*` class that client-side highlighters understand. Everything
+/// else becomes paragraphs.
+fn render_text(html: &mut String, text: &str) {
+    let mut paragraph: Vec<&str> = Vec::new();
+    let mut code: Option<(String, Vec<&str>)> = None;
+
+    for line in text.lines() {
+        let fence = line.trim_start().strip_prefix("```");
+        match (&mut code, fence) {
+            (Some((lang, lines)), Some(_)) => {
+                render_code_block(html, lang, lines);
+                code = None;
+            }
+            (Some((_, lines)), None) => lines.push(line),
+            (None, Some(lang)) => {
+                flush_paragraph(html, &mut paragraph);
+                code = Some((lang.trim().to_string(), Vec::new()));
+            }
+            (None, None) if line.trim().is_empty() => flush_paragraph(html, &mut paragraph),
+            (None, None) => paragraph.push(line),
+        }
+    }
+
+    // An unterminated fence still renders as code rather than losing content.
+    if let Some((lang, lines)) = &code {
+        render_code_block(html, lang, lines);
+    }
+    flush_paragraph(html, &mut paragraph);
+}
+
+fn render_code_block(html: &mut String, lang: &str, lines: &[&str]) {
+    let body = escape_html(&lines.join("\n"));
+    if lang.is_empty() {
+        let _ = writeln!(html, "<pre><code>{body}</code></pre>");
+    } else {
+        let lang = escape_html(lang);
+        let _ = writeln!(
+            html,
+            "<pre><code class=\"language-{lang}\">{body}</code></pre>"
+        );
+    }
+}
+
+fn flush_paragraph(html: &mut String, paragraph: &mut Vec<&str>) {
+    if paragraph.is_empty() {
+        return;
+    }
+    let text = escape_html(&paragraph.join("\n")).replace('\n', "<br>\n");
+    let _ = writeln!(html, "<p>{text}</p>");
+    paragraph.clear();
+}
+
+fn render_image(html: &mut String, image_url: &str) {
+    let url = escape_html(image_url);
+    if image_url.starts_with("data:") {
+        let _ = writeln!(html, "<img src=\"{url}\" alt=\"attached image\">");
+    } else {
+        // Remote images would make the transcript depend on the network, so
+        // link to them instead of embedding.
+        let _ = writeln!(html, "<p><a href=\"{url}\">{url}</a></p>");
+    }
+}
+
+/// Renders `items` as a Markdown transcript: messages become headed
+/// sections, tool calls fenced code blocks and tool outputs collapsible
+/// `<details>` blocks. Reasoning is left out.
+pub fn export_markdown(items: &[ResponseItem], meta: &SessionMeta) -> String {
+    let mut md = String::new();
+    let _ = writeln!(md, "# Codex session {}\n", meta.id);
+    let _ = writeln!(md, "_{}_\n", meta.timestamp);
+
+    for item in items {
+        render_markdown_item(&mut md, item);
+    }
+    md
+}
+
+fn render_markdown_item(md: &mut String, item: &ResponseItem) {
+    match item {
+        ResponseItem::Message { role, content, .. } => {
+            let _ = writeln!(md, "## {}\n", capitalize(role));
+            for content_item in content {
+                match content_item {
+                    ContentItem::InputText { text } | ContentItem::OutputText { text } => {
+                        let _ = writeln!(md, "{}\n", text.trim_end());
+                    }
+                    // Inline image data would drown the transcript.
+                    ContentItem::InputImage { image_url, .. } if image_url.starts_with("data:") => {
+                        md.push_str("_[image attached]_\n\n");
+                    }
+                    ContentItem::InputImage { image_url, .. } => {
+                        let _ = writeln!(md, "![image]({image_url})\n");
+                    }
+                }
+            }
+        }
+        ResponseItem::LocalShellCall { action, .. } => {
+            let LocalShellAction::Exec(exec) = action;
+            md.push_str("### Shell command\n\n");
+            push_code_block(md, "sh", &exec.command.join(" "));
+        }
+        ResponseItem::FunctionCall {
+            name, arguments, ..
+        } => {
+            let _ = writeln!(md, "### Function call: `{name}`\n");
+            push_code_block(md, "json", arguments);
+        }
+        ResponseItem::FunctionCallOutput { output, .. } => {
+            md.push_str("<details>\n<summary>Output</summary>\n\n");
+            push_code_block(md, "", &output.content);
+            md.push_str("</details>\n\n");
+        }
+        ResponseItem::Reasoning { .. } | ResponseItem::Other => {}
+    }
+}
+
+/// Writes `body` as a fenced code block. The fence is made longer than any
+/// backtick run in `body` so that embedded fences cannot close it early.
+fn push_code_block(md: &mut String, lang: &str, body: &str) {
+    let longest_run = body.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+    let fence = "`".repeat(longest_run.max(2) + 1);
+    let _ = writeln!(md, "{fence}{lang}\n{}\n{fence}\n", body.trim_end());
+}
+
+fn capitalize(s: &str) -> String {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// Converts `items` into a single example in the OpenAI chat fine-tuning
+/// format: one line of JSON, `{"messages":[...]}`, terminated by a newline.
+///
+/// Function calls become `tool_calls` on an assistant message; calls that
+/// directly follow an assistant message are merged into it. Outputs become
+/// `tool` messages. Local shell calls are expressed as calls to the `shell`
+/// function, since the format has no equivalent. Dropped are reasoning
+/// items, unrecognized items, and images (messages left without any text are
+/// dropped entirely). Returns an empty string if nothing is left.
+pub fn export_finetune_jsonl(items: &[ResponseItem]) -> String {
+    let mut messages: Vec<Value> = Vec::new();
+    for item in items {
+        match item {
+            ResponseItem::Message { role, content, .. } => {
+                let text: String = content
+                    .iter()
+                    .filter_map(|c| match c {
+                        ContentItem::InputText { text } | ContentItem::OutputText { text } => {
+                            Some(text.as_str())
+                        }
+                        ContentItem::InputImage { .. } => None,
+                    })
+                    .collect();
+                if !text.is_empty() {
+                    messages.push(json!({"role": role, "content": text}));
+                }
+            }
+            ResponseItem::FunctionCall {
+                name,
+                arguments,
+                call_id,
+            } => push_tool_call(&mut messages, call_id, name, arguments),
+            ResponseItem::LocalShellCall {
+                id,
+                call_id,
+                action,
+                ..
+            } => {
+                let LocalShellAction::Exec(exec) = action;
+                let arguments = json!({
+                    "command": exec.command,
+                    "workdir": exec.working_directory,
+                    "timeout": exec.timeout_ms,
+                })
+                .to_string();
+                let call_id = call_id
+                    .as_ref()
+                    .or(id.as_ref())
+                    .cloned()
+                    .unwrap_or_default();
+                push_tool_call(&mut messages, &call_id, "shell", &arguments);
+            }
+            ResponseItem::FunctionCallOutput { call_id, output } => {
+                messages.push(json!({
+                    "role": "tool",
+                    "tool_call_id": call_id,
+                    "content": output.content,
+                }));
+            }
+            ResponseItem::Reasoning { .. } | ResponseItem::Other => {}
+        }
+    }
+
+    if messages.is_empty() {
+        return String::new();
+    }
+    format!("{}\n", json!({ "messages": messages }))
+}
+
+fn push_tool_call(messages: &mut Vec<Value>, call_id: &str, name: &str, arguments: &str) {
+    let tool_call = json!({
+        "id": call_id,
+        "type": "function",
+        "function": {"name": name, "arguments": arguments},
+    });
+    if let Some(last) = messages.last_mut().filter(|m| m["role"] == "assistant") {
+        match last.get_mut("tool_calls").and_then(Value::as_array_mut) {
+            Some(tool_calls) => tool_calls.push(tool_call),
+            None => last["tool_calls"] = json!([tool_call]),
+        }
+        return;
+    }
+    messages.push(json!({
+        "role": "assistant",
+        "content": null,
+        "tool_calls": [tool_call],
+    }));
+}
+
+fn escape_html(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+    use super::*;
+    use crate::models::FunctionCallOutputPayload;
+    use pretty_assertions::assert_eq;
+    use uuid::Uuid;
+
+    #[test]
+    fn html_embeds_session_id_and_images() {
+        let meta = SessionMeta {
+            id: Uuid::new_v4(),
+            timestamp: "2025-05-07T17:24:21.123Z".to_string(),
+            instructions: None,
+            ..Default::default()
+        };
+        let image_url = "data:image/png;base64,iVBORw0KGgo=".to_string();
+        let items = vec![
+            ResponseItem::Message {
+                role: "user".to_string(),
+                content: vec![
+                    ContentItem::InputText {
+                        text: "What is <this>?".to_string(),
+                    },
+                    ContentItem::InputImage {
+                        image_url: image_url.clone(),
+                        detail: None,
+                    },
+                ],
+                name: None,
+            },
+            ResponseItem::Message {
+                role: "assistant".to_string(),
+                content: vec![ContentItem::OutputText {
+                    text: "A logo.\n\n```rust\nfn main() {}\n```".to_string(),
+                }],
+                name: None,
+            },
+        ];
+
+        let html = export_html(&meta, &items);
+
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains(&meta.id.to_string()));
+        assert!(html.contains(&format!("<img src=\"{image_url}\"")));
+        assert!(html.contains("What is &lt;this&gt;?"));
+        assert!(html.contains("<pre><code class=\"language-rust\">fn main() {}</code></pre>"));
+    }
+
+    #[test]
+    fn markdown_transcript_matches_golden() {
+        let meta = SessionMeta {
+            id: Uuid::nil(),
+            timestamp: "2025-05-07T17:24:21.123Z".to_string(),
+            ..Default::default()
+        };
+        let items = vec![
+            ResponseItem::Message {
+                role: "user".to_string(),
+                content: vec![ContentItem::InputText {
+                    text: "List the files.".to_string(),
+                }],
+                name: None,
+            },
+            ResponseItem::Reasoning {
+                id: "rs_1".to_string(),
+                summary: vec![ReasoningItemReasoningSummary::SummaryText {
+                    text: "Run ls.".to_string(),
+                }],
+            },
+            ResponseItem::FunctionCall {
+                name: "shell".to_string(),
+                arguments: r#"{"command":["ls"]}"#.to_string(),
+                call_id: "call_1".to_string(),
+            },
+            ResponseItem::FunctionCallOutput {
+                call_id: "call_1".to_string(),
+                output: FunctionCallOutputPayload {
+                    content: "Cargo.toml\nsrc\n".to_string(),
+                    success: Some(true),
+                    exit_code: None,
+                    duration_ms: None,
+                },
+            },
+            ResponseItem::Message {
+                role: "assistant".to_string(),
+                content: vec![ContentItem::OutputText {
+                    text: "There is a manifest:\n\n```toml\n[package]\n```".to_string(),
+                }],
+                name: None,
+            },
+            ResponseItem::Other,
+        ];
+
+        let expected = r#"# Codex session 00000000-0000-0000-0000-000000000000
+
+_2025-05-07T17:24:21.123Z_
+
+## User
+
+List the files.
+
+### Function call: `shell`
+
+```json
+{"command":["ls"]}
+```
+
+<details>
+<summary>Output</summary>
+
+```
+Cargo.toml
+src
+```
+
+</details>
+
+## Assistant
+
+There is a manifest:
+
+```toml
+[package]
+```
+
+"#;
+        assert_eq!(expected, export_markdown(&items, &meta));
+    }
+
+    #[test]
+    fn markdown_fence_outlasts_backticks_in_body() {
+        let mut md = String::new();
+        push_code_block(&mut md, "", "```\nnested\n```");
+        assert_eq!("````\n```\nnested\n```\n````\n\n", md);
+    }
+
+    #[test]
+    fn finetune_example_collapses_tool_call_and_output() {
+        let items = vec![
+            ResponseItem::Message {
+                role: "user".to_string(),
+                content: vec![ContentItem::InputText {
+                    text: "List the files.".to_string(),
+                }],
+                name: None,
+            },
+            ResponseItem::FunctionCall {
+                name: "shell".to_string(),
+                arguments: r#"{"command":["ls"]}"#.to_string(),
+                call_id: "call_1".to_string(),
+            },
+            ResponseItem::FunctionCallOutput {
+                call_id: "call_1".to_string(),
+                output: FunctionCallOutputPayload {
+                    content: "Cargo.toml".to_string(),
+                    success: Some(true),
+                    exit_code: None,
+                    duration_ms: None,
+                },
+            },
+        ];
+
+        let jsonl = export_finetune_jsonl(&items);
+
+        assert_eq!(1, jsonl.lines().count());
+        assert!(jsonl.ends_with('\n'));
+        let example: Value = serde_json::from_str(jsonl.trim_end()).unwrap();
+        assert_eq!(
+            json!({"messages": [
+                {"role": "user", "content": "List the files."},
+                {
+                    "role": "assistant",
+                    "content": null,
+                    "tool_calls": [{
+                        "id": "call_1",
+                        "type": "function",
+                        "function": {"name": "shell", "arguments": "{\"command\":[\"ls\"]}"},
+                    }],
+                },
+                {"role": "tool", "tool_call_id": "call_1", "content": "Cargo.toml"},
+            ]}),
+            example
+        );
+    }
+
+    #[test]
+    fn unterminated_code_fence_is_kept() {
+        let mut html = String::new();
+        render_text(&mut html, "```\nlet x = 1;");
+        assert_eq!("<pre><code>let x = 1;</code></pre>\n", html);
+    }
+}
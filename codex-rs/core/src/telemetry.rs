@@ -0,0 +1,467 @@
+//! Lightweight, process-wide metrics that do not depend on any tracing or
+//! exporter backend.
+//!
+//! Token usage is accumulated into global atomic counters every time the
+//! model reports it, so embedders can read running totals with
+//! [`token_totals`] regardless of how (or whether) spans are exported. Tool
+//! invocations are counted per tool name and read with [`tool_call_counts`],
+//! and retried model requests with [`llm_retries`].
+//! Per-request token counts are also bucketed into histograms per model, read
+//! with [`token_histograms`], so percentiles can be derived.
+//! With the `prometheus` feature the same counters can be rendered for a
+//! `/metrics` endpoint via `metrics_text`.
+
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::sync::PoisonError;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+
+use crate::protocol::TokenUsage;
+
+static TOKEN_COUNTERS: TokenCounters = TokenCounters::new();
+static TOOL_CALL_COUNTERS: ToolCallCounters = ToolCallCounters::new();
+static LLM_RETRIES: AtomicU64 = AtomicU64::new(0);
+static TOKEN_HISTOGRAMS: TokenHistogramsByModel = TokenHistogramsByModel::new();
+
+/// Upper bounds of the token histogram buckets. Observations above the last
+/// bound are only reflected in the histogram's `count` and `sum`.
+pub const TOKEN_BUCKETS: [u64; 8] = [256, 1024, 4096, 16384, 32768, 65536, 131072, 262144];
+
+/// Point-in-time snapshot of the token counters.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TokenTotals {
+    /// Number of model responses that reported usage.
+    pub requests: u64,
+    pub input_tokens: u64,
+    pub cached_input_tokens: u64,
+    pub output_tokens: u64,
+    pub reasoning_output_tokens: u64,
+    pub total_tokens: u64,
+}
+
+/// Set of monotonically increasing token counters. Updates are lock-free so
+/// recording from multiple sessions or threads never blocks.
+#[derive(Debug, Default)]
+pub(crate) struct TokenCounters {
+    requests: AtomicU64,
+    input_tokens: AtomicU64,
+    cached_input_tokens: AtomicU64,
+    output_tokens: AtomicU64,
+    reasoning_output_tokens: AtomicU64,
+    total_tokens: AtomicU64,
+}
+
+impl TokenCounters {
+    pub(crate) const fn new() -> Self {
+        Self {
+            requests: AtomicU64::new(0),
+            input_tokens: AtomicU64::new(0),
+            cached_input_tokens: AtomicU64::new(0),
+            output_tokens: AtomicU64::new(0),
+            reasoning_output_tokens: AtomicU64::new(0),
+            total_tokens: AtomicU64::new(0),
+        }
+    }
+
+    pub(crate) fn record(&self, usage: &TokenUsage) {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        self.input_tokens
+            .fetch_add(usage.input_tokens, Ordering::Relaxed);
+        self.cached_input_tokens
+            .fetch_add(usage.cached_input_tokens.unwrap_or(0), Ordering::Relaxed);
+        self.output_tokens
+            .fetch_add(usage.output_tokens, Ordering::Relaxed);
+        self.reasoning_output_tokens.fetch_add(
+            usage.reasoning_output_tokens.unwrap_or(0),
+            Ordering::Relaxed,
+        );
+        self.total_tokens
+            .fetch_add(usage.total_tokens, Ordering::Relaxed);
+    }
+
+    pub(crate) fn snapshot(&self) -> TokenTotals {
+        TokenTotals {
+            requests: self.requests.load(Ordering::Relaxed),
+            input_tokens: self.input_tokens.load(Ordering::Relaxed),
+            cached_input_tokens: self.cached_input_tokens.load(Ordering::Relaxed),
+            output_tokens: self.output_tokens.load(Ordering::Relaxed),
+            reasoning_output_tokens: self.reasoning_output_tokens.load(Ordering::Relaxed),
+            total_tokens: self.total_tokens.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Number of calls per tool name. The set of tools is open-ended (MCP
+/// servers add their own), so unlike the token counters this is a map behind
+/// a lock; it is only touched once per tool call.
+#[derive(Debug, Default)]
+pub(crate) struct ToolCallCounters {
+    counts: Mutex<BTreeMap<String, u64>>,
+}
+
+impl ToolCallCounters {
+    pub(crate) const fn new() -> Self {
+        Self {
+            counts: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    pub(crate) fn record(&self, tool: &str) {
+        let mut counts = self.counts.lock().unwrap_or_else(PoisonError::into_inner);
+        *counts.entry(tool.to_string()).or_default() += 1;
+    }
+
+    pub(crate) fn snapshot(&self) -> BTreeMap<String, u64> {
+        self.counts
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+}
+
+/// Distribution of a value over [`TOKEN_BUCKETS`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Histogram {
+    /// Observations that fell into each bucket (not cumulative), in the order
+    /// of [`TOKEN_BUCKETS`].
+    pub buckets: [u64; TOKEN_BUCKETS.len()],
+    pub sum: u64,
+    pub count: u64,
+}
+
+impl Histogram {
+    fn observe(&mut self, value: u64) {
+        if let Some(bucket) = TOKEN_BUCKETS.iter().position(|bound| value <= *bound) {
+            self.buckets[bucket] += 1;
+        }
+        self.sum += value;
+        self.count += 1;
+    }
+}
+
+/// Token histograms for the requests made with one model.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TokenHistograms {
+    pub input_tokens: Histogram,
+    pub output_tokens: Histogram,
+    pub total_tokens: Histogram,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct TokenHistogramsByModel {
+    models: Mutex<BTreeMap<String, TokenHistograms>>,
+}
+
+impl TokenHistogramsByModel {
+    pub(crate) const fn new() -> Self {
+        Self {
+            models: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    pub(crate) fn record(&self, model: &str, usage: &TokenUsage) {
+        let mut models = self.models.lock().unwrap_or_else(PoisonError::into_inner);
+        let histograms = models.entry(model.to_string()).or_default();
+        histograms.input_tokens.observe(usage.input_tokens);
+        histograms.output_tokens.observe(usage.output_tokens);
+        histograms.total_tokens.observe(usage.total_tokens);
+    }
+
+    pub(crate) fn snapshot(&self) -> BTreeMap<String, TokenHistograms> {
+        self.models
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+}
+
+/// Adds `usage` to the process-wide token counters.
+pub fn record_token_usage(usage: &TokenUsage) {
+    TOKEN_COUNTERS.record(usage);
+}
+
+/// Returns the tokens recorded since the process started.
+pub fn token_totals() -> TokenTotals {
+    TOKEN_COUNTERS.snapshot()
+}
+
+/// Adds the token counts of one response from `model` to the histograms.
+pub fn record_token_histograms(model: &str, usage: &TokenUsage) {
+    TOKEN_HISTOGRAMS.record(model, usage);
+}
+
+/// Returns the token histograms per model since the process started.
+pub fn token_histograms() -> BTreeMap<String, TokenHistograms> {
+    TOKEN_HISTOGRAMS.snapshot()
+}
+
+/// Counts one retry of a model request, i.e. an attempt after a failed one.
+pub fn record_llm_retry() {
+    LLM_RETRIES.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Returns the number of model request retries since the process started.
+pub fn llm_retries() -> u64 {
+    LLM_RETRIES.load(Ordering::Relaxed)
+}
+
+/// Counts one invocation of `tool`, named as the model called it.
+pub fn record_tool_call(tool: &str) {
+    TOOL_CALL_COUNTERS.record(tool);
+}
+
+/// Returns the number of calls per tool since the process started.
+pub fn tool_call_counts() -> BTreeMap<String, u64> {
+    TOOL_CALL_COUNTERS.snapshot()
+}
+
+/// Renders the process-wide counters in the Prometheus text exposition
+/// format, ready to be served from a `/metrics` endpoint.
+#[cfg(feature = "prometheus")]
+pub fn metrics_text() -> String {
+    render_metrics(
+        &token_totals(),
+        llm_retries(),
+        &tool_call_counts(),
+        &token_histograms(),
+    )
+}
+
+#[cfg(feature = "prometheus")]
+fn render_metrics(
+    totals: &TokenTotals,
+    llm_retries: u64,
+    tool_calls: &BTreeMap<String, u64>,
+    histograms: &BTreeMap<String, TokenHistograms>,
+) -> String {
+    use std::fmt::Write as _;
+
+    let metrics = [
+        (
+            "codex_requests_total",
+            "Model responses that reported token usage.",
+            totals.requests,
+        ),
+        (
+            "codex_input_tokens_total",
+            "Prompt tokens sent to the model.",
+            totals.input_tokens,
+        ),
+        (
+            "codex_cached_input_tokens_total",
+            "Prompt tokens served from the provider's cache.",
+            totals.cached_input_tokens,
+        ),
+        (
+            "codex_output_tokens_total",
+            "Completion tokens generated by the model.",
+            totals.output_tokens,
+        ),
+        (
+            "codex_reasoning_output_tokens_total",
+            "Completion tokens spent on reasoning.",
+            totals.reasoning_output_tokens,
+        ),
+        (
+            "codex_tokens_total",
+            "Total tokens reported by the model.",
+            totals.total_tokens,
+        ),
+        (
+            "codex_llm_retries_total",
+            "Model requests retried after a failed attempt.",
+            llm_retries,
+        ),
+    ];
+
+    let mut text = String::new();
+    for (name, help, value) in metrics {
+        let _ = writeln!(text, "# HELP {name} {help}");
+        let _ = writeln!(text, "# TYPE {name} counter");
+        let _ = writeln!(text, "{name} {value}");
+    }
+
+    let _ = writeln!(
+        text,
+        "# HELP codex_tool_calls_total Tool calls made by the model."
+    );
+    let _ = writeln!(text, "# TYPE codex_tool_calls_total counter");
+    for (tool, count) in tool_calls {
+        let tool = escape_label(tool);
+        let _ = writeln!(text, "codex_tool_calls_total{{tool=\"{tool}\"}} {count}");
+    }
+
+    for (name, help, histogram) in [
+        (
+            "codex_llm_input_tokens",
+            "Prompt tokens per model response.",
+            (|h: &TokenHistograms| &h.input_tokens) as fn(&TokenHistograms) -> &Histogram,
+        ),
+        (
+            "codex_llm_output_tokens",
+            "Completion tokens per model response.",
+            |h| &h.output_tokens,
+        ),
+        (
+            "codex_llm_total_tokens",
+            "Total tokens per model response.",
+            |h| &h.total_tokens,
+        ),
+    ] {
+        let _ = writeln!(text, "# HELP {name} {help}");
+        let _ = writeln!(text, "# TYPE {name} histogram");
+        for (model, histograms) in histograms {
+            let model = escape_label(model);
+            let histogram = histogram(histograms);
+            let mut cumulative = 0;
+            for (bound, observations) in TOKEN_BUCKETS.iter().zip(histogram.buckets) {
+                cumulative += observations;
+                let _ = writeln!(
+                    text,
+                    "{name}_bucket{{model=\"{model}\",le=\"{bound}\"}} {cumulative}"
+                );
+            }
+            let count = histogram.count;
+            let _ = writeln!(
+                text,
+                "{name}_bucket{{model=\"{model}\",le=\"+Inf\"}} {count}"
+            );
+            let _ = writeln!(text, "{name}_sum{{model=\"{model}\"}} {}", histogram.sum);
+            let _ = writeln!(text, "{name}_count{{model=\"{model}\"}} {count}");
+        }
+    }
+    text
+}
+
+/// Escapes a Prometheus label value.
+#[cfg(feature = "prometheus")]
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::sync::Arc;
+
+    fn usage(input: u64, output: u64) -> TokenUsage {
+        TokenUsage {
+            input_tokens: input,
+            cached_input_tokens: Some(1),
+            output_tokens: output,
+            reasoning_output_tokens: None,
+            total_tokens: input + output,
+        }
+    }
+
+    #[test]
+    fn recording_twice_sums() {
+        let counters = TokenCounters::new();
+        counters.record(&usage(10, 5));
+        counters.record(&usage(7, 3));
+
+        assert_eq!(
+            TokenTotals {
+                requests: 2,
+                input_tokens: 17,
+                cached_input_tokens: 2,
+                output_tokens: 8,
+                reasoning_output_tokens: 0,
+                total_tokens: 25,
+            },
+            counters.snapshot()
+        );
+    }
+
+    #[test]
+    fn concurrent_recording_is_not_lost() {
+        let counters = Arc::new(TokenCounters::new());
+        let handles: Vec<_> = (0..8)
+            .map(|_| {
+                let counters = Arc::clone(&counters);
+                std::thread::spawn(move || {
+                    for _ in 0..100 {
+                        counters.record(&usage(2, 1));
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let totals = counters.snapshot();
+        assert_eq!(totals.input_tokens, 1_600);
+        assert_eq!(totals.output_tokens, 800);
+        assert_eq!(totals.total_tokens, 2_400);
+    }
+
+    #[test]
+    fn global_counters_accumulate() {
+        let before = token_totals();
+        record_token_usage(&usage(4, 2));
+        let after = token_totals();
+
+        // Other tests may record concurrently, so only a lower bound holds.
+        assert!(after.total_tokens >= before.total_tokens + 6);
+    }
+
+    #[test]
+    fn tool_calls_are_counted_per_tool() {
+        let counters = ToolCallCounters::new();
+        counters.record("apply_patch");
+        counters.record("shell");
+        counters.record("apply_patch");
+
+        assert_eq!(
+            BTreeMap::from([("apply_patch".to_string(), 2), ("shell".to_string(), 1)]),
+            counters.snapshot()
+        );
+    }
+
+    #[test]
+    fn token_histograms_record_each_response() {
+        let histograms = TokenHistogramsByModel::new();
+        histograms.record("o3", &usage(300, 100));
+        histograms.record("o3", &usage(5000, 200));
+
+        let o3 = &histograms.snapshot()["o3"];
+        assert_eq!(o3.total_tokens.count, 2);
+        assert_eq!(o3.total_tokens.sum, 5600);
+        assert_eq!(o3.total_tokens.buckets, [0, 1, 0, 1, 0, 0, 0, 0]);
+        assert_eq!(o3.output_tokens.buckets[0], 2);
+    }
+
+    #[cfg(feature = "prometheus")]
+    #[test]
+    fn metrics_text_renders_counter_values() {
+        let counters = TokenCounters::new();
+        counters.record(&usage(10, 5));
+        counters.record(&usage(7, 3));
+
+        let tool_calls = BTreeMap::from([("shell".to_string(), 3)]);
+
+        let histograms = TokenHistogramsByModel::new();
+        histograms.record("o3", &usage(300, 100));
+
+        let text = render_metrics(&counters.snapshot(), 4, &tool_calls, &histograms.snapshot());
+
+        assert!(text.contains("# TYPE codex_tokens_total counter\ncodex_tokens_total 25\n"));
+        assert!(text.contains("\ncodex_requests_total 2\n"));
+        assert!(text.contains("\ncodex_input_tokens_total 17\n"));
+        assert!(text.contains("\ncodex_output_tokens_total 8\n"));
+        assert!(text.contains("\ncodex_cached_input_tokens_total 2\n"));
+        assert!(text.contains("\ncodex_llm_retries_total 4\n"));
+        assert!(text.contains("\ncodex_tool_calls_total{tool=\"shell\"} 3\n"));
+        assert!(text.contains("\ncodex_llm_total_tokens_bucket{model=\"o3\",le=\"256\"} 0\n"));
+        assert!(text.contains("\ncodex_llm_total_tokens_bucket{model=\"o3\",le=\"1024\"} 1\n"));
+        assert!(text.contains("\ncodex_llm_total_tokens_bucket{model=\"o3\",le=\"+Inf\"} 1\n"));
+        assert!(text.contains("\ncodex_llm_total_tokens_sum{model=\"o3\"} 400\n"));
+    }
+}
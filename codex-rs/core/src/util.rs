@@ -1,11 +1,16 @@
+use std::path::Path;
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::sync::LazyLock;
 use std::time::Duration;
 
 use rand::Rng;
+use regex::Regex;
 use tokio::sync::Notify;
 use tracing::debug;
 
 use crate::config::Config;
+use crate::protocol::InputItem;
 
 const INITIAL_DELAY_MS: u64 = 200;
 const BACKOFF_FACTOR: f64 = 1.3;
@@ -64,3 +69,165 @@ pub fn is_inside_git_repo(config: &Config) -> bool {
 
     false
 }
+
+/// Image formats accepted by the model, by file extension.
+const IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "gif", "webp"];
+
+/// Markdown image syntax, `![alt](path)`, capturing the path.
+#[allow(clippy::unwrap_used)]
+static MARKDOWN_IMAGE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"!\[[^\]]*\]\(\s*([^)\s]+)\s*\)").unwrap());
+
+/// Splits a pasted message into text and local image parts, in order. Images
+/// are referenced either with Markdown image syntax or as bare paths with an
+/// image extension; relative paths are resolved against `base_dir`. A
+/// reference only becomes an [`InputItem::LocalImage`] if the file exists,
+/// otherwise it is left in the surrounding [`InputItem::Text`].
+pub fn parse_mixed_input(raw: &str, base_dir: &Path) -> Vec<InputItem> {
+    let mut items = Vec::new();
+    let mut text = String::new();
+    let mut rest = 0;
+
+    for caps in MARKDOWN_IMAGE.captures_iter(raw) {
+        let (Some(whole), Some(path)) = (caps.get(0), caps.get(1)) else {
+            continue;
+        };
+        split_bare_image_paths(&raw[rest..whole.start()], base_dir, &mut text, &mut items);
+        match existing_image(path.as_str(), base_dir) {
+            Some(path) => {
+                push_text(&mut items, &mut text);
+                items.push(InputItem::LocalImage { path, detail: None });
+            }
+            None => text.push_str(whole.as_str()),
+        }
+        rest = whole.end();
+    }
+    split_bare_image_paths(&raw[rest..], base_dir, &mut text, &mut items);
+    push_text(&mut items, &mut text);
+    items
+}
+
+/// Appends `segment` to `text`, except for whitespace-separated words that
+/// name an existing image, which end the current text part and become images.
+/// Quotes and punctuation around a path stay with the text.
+fn split_bare_image_paths(
+    segment: &str,
+    base_dir: &Path,
+    text: &mut String,
+    items: &mut Vec<InputItem>,
+) {
+    let mut rest = 0;
+    let mut word_start = 0;
+    for word in segment.split(|c: char| c.is_ascii_whitespace()) {
+        let start = word_start;
+        word_start += word.len() + 1;
+        let unquoted = word.trim_start_matches(['"', '\'', '`', '(', '<']);
+        let candidate =
+            unquoted.trim_end_matches(['"', '\'', '`', ')', '>', '.', ',', ';', ':', '!', '?']);
+        let Some(path) = existing_image(candidate, base_dir) else {
+            continue;
+        };
+        let candidate_start = start + word.len() - unquoted.len();
+        text.push_str(&segment[rest..candidate_start]);
+        push_text(items, text);
+        items.push(InputItem::LocalImage { path, detail: None });
+        rest = candidate_start + candidate.len();
+    }
+    text.push_str(&segment[rest..]);
+}
+
+/// Resolves `candidate` against `base_dir` and returns it if it names an
+/// existing file with an image extension.
+fn existing_image(candidate: &str, base_dir: &Path) -> Option<PathBuf> {
+    let extension = Path::new(candidate).extension()?.to_str()?;
+    if !IMAGE_EXTENSIONS.contains(&extension.to_ascii_lowercase().as_str()) {
+        return None;
+    }
+    let path = base_dir.join(candidate);
+    path.is_file().then_some(path)
+}
+
+/// Moves the accumulated `text`, trimmed, into `items` unless it is blank.
+fn push_text(items: &mut Vec<InputItem>, text: &mut String) {
+    let trimmed = text.trim();
+    if !trimmed.is_empty() {
+        items.push(InputItem::Text {
+            text: trimmed.to_string(),
+        });
+    }
+    text.clear();
+}
+
+/// Removes a leading UTF-8 byte order mark, which some editors on Windows
+/// prepend to text files.
+pub(crate) fn strip_bom(s: &str) -> &str {
+    s.strip_prefix('\u{feff}').unwrap_or(s)
+}
+
+/// Normalizes instruction text authored on any platform: strips a BOM,
+/// converts CRLF to LF and trims trailing whitespace from each line. Blank
+/// lines, including a final newline, are kept.
+pub(crate) fn normalize_instructions(s: &str) -> String {
+    let s = strip_bom(s);
+    let mut out = s.lines().map(str::trim_end).collect::<Vec<_>>().join("\n");
+    if s.ends_with('\n') {
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use tempfile::TempDir;
+
+    #[test]
+    fn splits_prose_around_image_paths() {
+        let dir = TempDir::new().unwrap();
+        std::fs::write(dir.path().join("shot.png"), b"png").unwrap();
+        std::fs::write(dir.path().join("diagram.JPG"), b"jpg").unwrap();
+
+        let items = parse_mixed_input(
+            "Why is the button misaligned in shot.png? Compare ![before](diagram.JPG) please.",
+            dir.path(),
+        );
+
+        assert_eq!(
+            items,
+            vec![
+                InputItem::Text {
+                    text: "Why is the button misaligned in".to_string(),
+                },
+                InputItem::LocalImage {
+                    path: dir.path().join("shot.png"),
+                    detail: None,
+                },
+                InputItem::Text {
+                    text: "? Compare".to_string(),
+                },
+                InputItem::LocalImage {
+                    path: dir.path().join("diagram.JPG"),
+                    detail: None,
+                },
+                InputItem::Text {
+                    text: "please.".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn missing_images_stay_text() {
+        let dir = TempDir::new().unwrap();
+        let raw = "See missing.png and ![alt](gone.gif).";
+
+        assert_eq!(
+            parse_mixed_input(raw, dir.path()),
+            vec![InputItem::Text {
+                text: raw.to_string(),
+            }]
+        );
+    }
+}
@@ -68,6 +68,7 @@ async fn includes_session_id_and_model_headers_in_request() {
         request_max_retries: Some(0),
         stream_max_retries: Some(0),
         stream_idle_timeout_ms: None,
+        max_stop_sequences: None,
     };
 
     // Init session
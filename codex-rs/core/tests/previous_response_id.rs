@@ -106,6 +106,7 @@ async fn keeps_previous_response_id_between_tasks() {
         request_max_retries: Some(0),
         stream_max_retries: Some(0),
         stream_idle_timeout_ms: None,
+        max_stop_sequences: None,
     };
 
     // Init session
@@ -0,0 +1,127 @@
+//! Verifies that `escalate_reasoning_on_empty` re-issues a turn that came
+//! back without any visible output once, at a higher reasoning effort.
+
+use std::time::Duration;
+
+use codex_core::Codex;
+use codex_core::ModelProviderInfo;
+use codex_core::config_types::ReasoningEffort;
+use codex_core::exec::CODEX_SANDBOX_NETWORK_DISABLED_ENV_VAR;
+use codex_core::protocol::EventMsg;
+use codex_core::protocol::InputItem;
+use codex_core::protocol::Op;
+mod test_support;
+use tempfile::TempDir;
+use test_support::load_default_config_for_test;
+use test_support::load_sse_fixture_with_id;
+use tokio::time::timeout;
+use wiremock::Mock;
+use wiremock::MockServer;
+use wiremock::ResponseTemplate;
+use wiremock::matchers::method;
+use wiremock::matchers::path;
+
+fn sse_response(body: String) -> ResponseTemplate {
+    ResponseTemplate::new(200)
+        .insert_header("content-type", "text/event-stream")
+        .set_body_raw(body, "text/event-stream")
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn empty_response_is_retried_once_at_higher_effort() {
+    #![allow(clippy::unwrap_used)]
+
+    if std::env::var(CODEX_SANDBOX_NETWORK_DISABLED_ENV_VAR).is_ok() {
+        println!(
+            "Skipping test because it cannot execute when network is disabled in a Codex sandbox."
+        );
+        return;
+    }
+
+    let server = MockServer::start().await;
+    // The first request gets a response without any output...
+    Mock::given(method("POST"))
+        .and(path("/v1/responses"))
+        .respond_with(sse_response(load_sse_fixture_with_id(
+            "tests/fixtures/completed_template.json",
+            "resp_empty",
+        )))
+        .up_to_n_times(1)
+        .mount(&server)
+        .await;
+    // ...and the escalated retry gets an answer.
+    let answer = load_sse_fixture_with_id("tests/fixtures/message_template.json", "resp_answer")
+        .replace("__TEXT__", "42");
+    Mock::given(method("POST"))
+        .and(path("/v1/responses"))
+        .respond_with(sse_response(answer))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let codex_home = TempDir::new().unwrap();
+    let mut config = load_default_config_for_test(&codex_home);
+    config.model_provider = ModelProviderInfo {
+        name: "mock".into(),
+        base_url: format!("{}/v1", server.uri()),
+        // Environment variable that should exist in the test environment.
+        env_key: Some("PATH".into()),
+        env_key_instructions: None,
+        wire_api: codex_core::WireApi::Responses,
+        query_params: None,
+        http_headers: None,
+        env_http_headers: None,
+        request_max_retries: Some(0),
+        stream_max_retries: Some(0),
+        stream_idle_timeout_ms: Some(2000),
+        max_stop_sequences: None,
+    };
+    config.model_reasoning_effort = ReasoningEffort::Medium;
+    config.escalate_reasoning_on_empty = true;
+
+    let ctrl_c = std::sync::Arc::new(tokio::sync::Notify::new());
+    let (codex, _init_id, _session_id) = Codex::spawn(config, ctrl_c).await.unwrap();
+
+    codex
+        .submit(Op::UserInput {
+            items: vec![InputItem::Text {
+                text: "what is six times seven?".into(),
+            }],
+        })
+        .await
+        .unwrap();
+
+    let mut background = Vec::new();
+    let mut answer = None;
+    loop {
+        let ev = timeout(Duration::from_secs(10), codex.next_event())
+            .await
+            .unwrap()
+            .unwrap();
+        match ev.msg {
+            EventMsg::BackgroundEvent(ev) => background.push(ev.message),
+            EventMsg::AgentMessage(ev) => answer = Some(ev.message),
+            EventMsg::Error(ev) => panic!("unexpected error: {}", ev.message),
+            EventMsg::TaskComplete(_) => break,
+            _ => {}
+        }
+    }
+
+    assert_eq!(
+        background,
+        vec!["response was empty; retrying with reasoning effort `high` instead of `medium`"]
+    );
+    assert_eq!(answer.as_deref(), Some("42"));
+
+    let efforts: Vec<serde_json::Value> = server
+        .received_requests()
+        .await
+        .unwrap()
+        .iter()
+        .map(|req| {
+            let body: serde_json::Value = serde_json::from_slice(&req.body).unwrap();
+            body["reasoning"]["effort"].clone()
+        })
+        .collect();
+    assert_eq!(efforts, vec!["medium", "high"]);
+}
@@ -0,0 +1,104 @@
+//! Verifies that embedders can abort an in-flight turn through
+//! `ResponseStream::cancel` without dropping the stream.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use codex_core::ModelClient;
+use codex_core::ModelProviderInfo;
+use codex_core::Prompt;
+use codex_core::ResponseEvent;
+use codex_core::exec::CODEX_SANDBOX_NETWORK_DISABLED_ENV_VAR;
+use futures::StreamExt;
+mod test_support;
+use tempfile::TempDir;
+use test_support::load_default_config_for_test;
+use tokio::time::timeout;
+use uuid::Uuid;
+use wiremock::Mock;
+use wiremock::MockServer;
+use wiremock::ResponseTemplate;
+use wiremock::matchers::method;
+use wiremock::matchers::path;
+
+/// A text delta followed by `response.completed`, delivered in one body so
+/// both events are available before the test cancels.
+fn sse_delta_then_completed() -> String {
+    let delta = serde_json::json!({
+        "type": "response.output_text.delta",
+        "delta": "Hello",
+    });
+    let completed = serde_json::json!({
+        "type": "response.completed",
+        "response": {"id": "resp1", "output": []},
+    });
+    format!(
+        "event: response.output_text.delta\ndata: {delta}\n\n\
+         event: response.completed\ndata: {completed}\n\n"
+    )
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn cancel_ends_the_stream_before_buffered_events() {
+    #![allow(clippy::unwrap_used)]
+
+    if std::env::var(CODEX_SANDBOX_NETWORK_DISABLED_ENV_VAR).is_ok() {
+        println!(
+            "Skipping test because it cannot execute when network is disabled in a Codex sandbox."
+        );
+        return;
+    }
+
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/v1/responses"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("content-type", "text/event-stream")
+                .set_body_raw(sse_delta_then_completed(), "text/event-stream"),
+        )
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let provider = ModelProviderInfo {
+        name: "openai".into(),
+        base_url: format!("{}/v1", server.uri()),
+        // Environment variable that should exist in the test environment.
+        // ModelClient will return an error if the environment variable for the
+        // provider is not set.
+        env_key: Some("PATH".into()),
+        env_key_instructions: None,
+        wire_api: codex_core::WireApi::Responses,
+        query_params: None,
+        http_headers: None,
+        env_http_headers: None,
+        request_max_retries: Some(0),
+        stream_max_retries: Some(0),
+        stream_idle_timeout_ms: None,
+        max_stop_sequences: None,
+    };
+
+    let codex_home = TempDir::new().unwrap();
+    let mut config = load_default_config_for_test(&codex_home);
+    config.model_provider = provider.clone();
+    let effort = config.model_reasoning_effort;
+    let summary = config.model_reasoning_summary;
+    let client = ModelClient::new(Arc::new(config), provider, effort, summary, Uuid::new_v4());
+
+    let mut stream = client.stream(&Prompt::default()).await.unwrap();
+    let first = timeout(Duration::from_secs(10), stream.next())
+        .await
+        .unwrap();
+    assert!(
+        matches!(first, Some(Ok(ResponseEvent::OutputTextDelta(ref d))) if d == "Hello"),
+        "unexpected first event: {first:?}"
+    );
+
+    stream.cancel();
+
+    let next = timeout(Duration::from_secs(10), stream.next())
+        .await
+        .unwrap();
+    assert!(next.is_none(), "stream yielded {next:?} after cancel");
+}
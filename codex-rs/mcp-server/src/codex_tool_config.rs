@@ -146,6 +146,7 @@ impl CodexToolCallParam {
             sandbox_mode: sandbox.map(Into::into),
             model_provider: None,
             codex_linux_sandbox_exe,
+            rollout_dir: None,
         };
 
         let cli_overrides = cli_overrides